@@ -1,9 +1,24 @@
 
+use crate::chess_move::{ Move, MoveKind, };
+use crate::error::Error;
+use crate::game_result::GameResult;
 use crate::piece::Piece;
 use crate::player::Player;
 use crate::moves::MOVES;
 use crate::utils;
 
+mod fen;
+mod pawns;
+mod perft;
+mod san;
+mod see;
+mod snapshot;
+mod zobrist;
+
+pub use fen::CastlingRights;
+pub use pawns::PawnStructure;
+pub use snapshot::Snapshot;
+
 const PIECE_COUNT: usize = 16;
 
 mod index {
@@ -24,12 +39,30 @@ mod index {
             3 | 4 => Piece::Rook,
             5     => Piece::Queen,
             6 | 7 => Piece::Bishop,
-            8..   => Piece::Pawn,
-            _     => panic!(),
+            _     => Piece::Pawn,
         }
     }
 }
 
+// King and rook destinations when castling with rook at rpos. The king always
+// ends on the g-file or c-file and the rook next to it, also in Chess960.
+fn castling_dests(kpos: u64, rpos: u64) -> (u64, u64) {
+    let row = kpos.trailing_zeros() & !7;
+    if rpos > kpos {
+        (utils::bit((row + 6).into()), utils::bit((row + 5).into()))
+    } else {
+        (utils::bit((row + 2).into()), utils::bit((row + 3).into()))
+    }
+}
+
+// Move selecting castling with rook at rpos: the king destination, unless it
+// could be confused with a regular king move, in which case the rook position.
+// In standard chess this is always the king moving two positions.
+fn castling_move(kpos: u64, rpos: u64) -> u64 {
+    let (kdest, _) = castling_dests(kpos, rpos);
+    let dist = (kpos.trailing_zeros() as i32 - kdest.trailing_zeros() as i32).abs();
+    if dist >= 2 { kdest } else { rpos }
+}
 
 #[derive(Clone, Copy)]
 struct Team {
@@ -44,13 +77,108 @@ struct Team {
 
 impl Team {
     
-    fn mask(self: &Self) -> u64 {
+    fn mask(&self) -> u64 {
         let mut m = 0;
         for &p in &self.positions[..] {
             m |= p; 
         }
         m
     }
+
+    // Mask of pawns, not counting promoted pawns
+    fn pawns(&self) -> u64 {
+        let mut m = 0;
+        for id in index::PAWN {
+            if self.promotions[id].is_none() {
+                m |= self.positions[id];
+            }
+        }
+        m
+    }
+
+    // Team with ranks flipped, rank 1 becoming rank 8
+    fn mirrored(&self) -> Team {
+        Team {
+            positions: self.positions.map(u64::swap_bytes),
+            en_passant_pos: self.en_passant_pos.swap_bytes(),
+            did_move: self.did_move.swap_bytes(),
+            ..*self
+        }
+    }
+
+    // Slot of the unmoved rook closest to the king on the king's row,
+    // on the kingside (towards the h-file) or queenside
+    fn castling_rook(&self, kingside: bool) -> Option<usize> {
+
+        let kpos = self.positions[index::KING];
+        let side = utils::byte_mask(kpos.trailing_zeros() as usize) & match kingside {
+            true  => utils::fill_left_excl(kpos),
+            false => utils::fill_right_excl(kpos),
+        };
+
+        (index::ROOK[0]..=index::ROOK[1])
+            .filter(|&id| self.positions[id] & side & !self.did_move > 0)
+            .min_by_key(|&id| utils::ortho_ray_between_excl(kpos, self.positions[id]).count_ones())
+    }
+
+    // Slot of the rook castled with, if the king moving to mov is castling.
+    // King must not have moved.
+    fn castling_rook_for(&self, mov: u64) -> Option<usize> {
+        let kpos = self.positions[index::KING];
+        [true, false].into_iter()
+            .filter_map(|kingside| self.castling_rook(kingside))
+            .find(|&id| castling_move(kpos, self.positions[id]) == mov)
+    }
+
+    // Piece in slot id, taking promotions into account
+    fn piece(&self, id: usize) -> Piece {
+        match self.promotions[id] {
+            None => index::into_piece(id),
+            Some(piece) => piece,
+        }
+    }
+
+    // Assigns pieces to slots. Pieces that don't fit in their own slots
+    // are placed in free pawn slots as promotions.
+    fn place(&mut self, pieces: &[(Piece, u64)]) -> Result<(), Error> {
+
+        use index::*;
+
+        // Pawns first, remaining pawn slots are used for promotions
+        let mut pawns = PAWN.iter();
+        for &(_, bit) in pieces.iter().filter(|(p, _)| *p == Piece::Pawn) {
+            let &id = pawns.next().ok_or(Error::IllegalPosition)?;
+            self.positions[id] = bit;
+        }
+
+        for &(piece, bit) in pieces.iter().filter(|(p, _)| *p != Piece::Pawn) {
+
+            let slots: &[usize] = match piece {
+                Piece::King   => &[KING],
+                Piece::Knight => &KNIGHT,
+                Piece::Rook   => &ROOK,
+                Piece::Queen  => &[QUEEN],
+                Piece::Bishop => &BISHOP,
+                Piece::Pawn   => unreachable!(),
+            };
+
+            match slots.iter().find(|&&id| self.positions[id] == 0) {
+                Some(&id) => self.positions[id] = bit,
+                None => {
+                    // Only one king allowed
+                    if piece == Piece::King {
+                        return Err(Error::IllegalPosition);
+                    }
+                    let &id = pawns.next().ok_or(Error::IllegalPosition)?;
+                    self.positions[id] = bit;
+                    self.promotions[id] = Some(piece);
+                },
+            }
+        }
+
+        Ok(())
+    }
+
 }
 
 impl Default for Team {
@@ -68,11 +196,47 @@ impl Default for Team {
     }
 }
 
-#[derive(Default)]
+/// Why a position ends the game, see [Board::outcome].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The current player is in check and has no legal moves.
+    Checkmate,
+    /// The current player is not in check but has no legal moves.
+    Stalemate,
+    /// Neither player has sufficient material to checkmate.
+    InsufficientMaterial,
+}
+
+/// Whether the current player may castle on one side, see [Board::castling_status].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CastlingStatus {
+    /// Castling is a legal move.
+    Available,
+    /// Castling rights remain, but there are pieces between the king and the rook.
+    Blocked,
+    /// Castling rights remain, but the king is in check or would pass through
+    /// or land on an attacked position.
+    ThroughCheck,
+    /// The king or the rook has moved, or castling is disabled.
+    Lost,
+}
+
+/// Piece, its owner, its position and its legal destinations.
+pub type MoveMapEntry = (Piece, Player, (u8, u8), Vec<(u8, u8)>);
+
+/// Bitboard representation of the game, used for move generation.
+/// Pieces are identified by slot ids, positions and moves are represented
+/// by masks with bit `x + 8 * y` set.
+#[derive(Clone, Default)]
 pub struct Board {
     white: Team,
     black: Team,
     pub player: Player,
+    // half moves since last capture or pawn move
+    halfmove_clock:  u32,
+    // starts at 1, incremented after black moves
+    fullmove_number: u32,
+    castling_disabled: bool,
 }
 
 impl Board {
@@ -80,7 +244,11 @@ impl Board {
     pub fn new() -> Board {
 
         use { index::*, utils::*, };
-        let mut b = Board { player: Player::White, ..Default::default() };
+        let mut b = Board {
+            player: Player::White,
+            fullmove_number: 1,
+            ..Default::default()
+        };
 
         b.white.positions[ROOK[0]]   = flatten_bit(0, 0);
         b.white.positions[KNIGHT[0]] = flatten_bit(1, 0);
@@ -91,8 +259,8 @@ impl Board {
         b.white.positions[KNIGHT[1]] = flatten_bit(6, 0);
         b.white.positions[ROOK[1]]   = flatten_bit(7, 0);
         
-        for i in 0..8 {
-            b.white.positions[PAWN[i]] = flatten_bit(i as u8, 1);
+        for (i, &id) in PAWN.iter().enumerate() {
+            b.white.positions[id] = flatten_bit(i as u8, 1);
         }
 
         b.black.positions[ROOK[0]]   = flatten_bit(0, 7);
@@ -104,47 +272,444 @@ impl Board {
         b.black.positions[KNIGHT[1]] = flatten_bit(6, 7);
         b.black.positions[ROOK[1]]   = flatten_bit(7, 7);
         
-        for i in 0..8 {
-            b.black.positions[PAWN[i]] = flatten_bit(i as u8, 6);
+        for (i, &id) in PAWN.iter().enumerate() {
+            b.black.positions[id] = flatten_bit(i as u8, 6);
         }
 
         b
     }
 
-    pub fn white_iter(self: &Self) -> TeamIterator {
+    /// Creates a board from an array of squares indexed by `[y][x]`,
+    /// and the player to move. No castling is available, see [Board::set_castling_rights].
+    /// Returns [Error::IllegalPosition] if the position is not legal.
+    pub fn from_array(
+        squares: &[[Option<(Piece, Player)>; 8]; 8],
+        player: Player
+    ) -> Result<Board, Error> {
+
+        let mut white = Vec::new();
+        let mut black = Vec::new();
+
+        for (y, row) in squares.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                let bit = utils::flatten_bit(x as u8, y as u8);
+                match square {
+                    None => (),
+                    Some((piece, Player::White)) => white.push((*piece, bit)),
+                    Some((piece, Player::Black)) => black.push((*piece, bit)),
+                }
+            }
+        }
+
+        let mut b = Board {
+            player,
+            fullmove_number: 1,
+            ..Default::default()
+        };
+
+        b.white.place(&white)?;
+        b.black.place(&black)?;
+        b.white.king_moved = true;
+        b.black.king_moved = true;
+
+        b.validate()?;
+
+        Ok(b)
+    }
+
+    /// Returns true if both players have a king and the player not to move is
+    /// not in check. Cheaper than [Board::validate], which checks more.
+    pub fn is_legal_position(&self) -> bool {
+        self.white.positions[index::KING] > 0
+            && self.black.positions[index::KING] > 0
+            && !self.is_player_in_check(self.player.opponent())
+    }
+
+    /// Checks that each player has exactly one king, that the player not to move
+    /// is not in check, that no pawns are on the first or last rank, and that
+    /// no two pieces share a square.
+    /// Returns [Error::IllegalPosition] otherwise.
+    pub fn validate(&self) -> Result<(), Error> {
+
+        use Player::*;
+
+        let (curr_team, opp_team, opp_player) = match self.player {
+            White => (&self.white, &self.black, Black, ),
+            Black => (&self.black, &self.white, White, ),
+        };
+
+        const BACK_RANKS: u64 = 0xff000000000000ff;
+
+        let mut occupied = 0;
+
+        for team in [curr_team, opp_team] {
+
+            if team.positions[index::KING] == 0
+                || team.promotions.contains(&Some(Piece::King))
+            {
+                return Err(Error::IllegalPosition);
+            }
+
+            for (id, &p) in team.positions.iter().enumerate() {
+
+                if p.count_ones() > 1 || p & occupied > 0 {
+                    return Err(Error::IllegalPosition);
+                }
+                occupied |= p;
+
+                if id >= index::PAWN[0]
+                    && team.promotions[id].is_none()
+                    && p & BACK_RANKS > 0
+                {
+                    return Err(Error::IllegalPosition);
+                }
+            }
+        }
+
+        // Player not to move can't be in check
+        let checkers = Self::attackers(
+            opp_team.positions[index::KING],
+            opp_team.mask(),
+            curr_team.mask(),
+            &curr_team.positions,
+            &curr_team.promotions,
+            opp_player
+        );
+
+        if checkers > 0 {
+            return Err(Error::IllegalPosition);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of half moves since the last capture or pawn move.
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// Returns the number of the full move, starting at 1 and incremented after
+    /// each move by black.
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    /// Returns mask of all occupied positions.
+    pub fn occupied(&self) -> u64 {
+        self.white.mask() | self.black.mask()
+    }
+
+    /// Returns mask of positions occupied by pieces of `player`.
+    pub fn occupied_by(&self, player: Player) -> u64 {
+        match player {
+            Player::White => self.white.mask(),
+            Player::Black => self.black.mask(),
+        }
+    }
+
+    /// Returns true if neither player can possibly checkmate, i.e. only kings remain,
+    /// along with either a single knight or bishop, or only bishops on positions of the
+    /// same color. Promoted pawns count as the piece they were promoted to.
+    pub fn is_insufficient_material(&self) -> bool {
+
+        let white = self.all_counts(Player::White);
+        let black = self.all_counts(Player::Black);
+
+        use Piece::*;
+        for counts in [white, black] {
+            if counts[Pawn as usize] + counts[Rook as usize] + counts[Queen as usize] > 0 {
+                return false;
+            }
+        }
+
+        let knights = white[Knight as usize] + black[Knight as usize];
+        let bishops = white[Bishop as usize] + black[Bishop as usize];
+
+        if knights + bishops <= 1 {
+            return true;
+        }
+
+        if knights > 0 {
+            return false;
+        }
+
+        // Bishops on one color only
+        let colors = self.pieces()
+            .filter(|&(piece, _, _, _)| piece == Bishop)
+            .map(|(_, _, x, y)| (x + y) % 2)
+            .collect::<Vec<_>>();
+
+        colors.iter().all(|&c| c == colors[0])
+    }
+
+    /// Returns number of `piece`s `player` has, counting promoted pawns as
+    /// the piece they were promoted to.
+    pub fn piece_count(&self, player: Player, piece: Piece) -> u32 {
+        self.all_counts(player)[piece as usize]
+    }
+
+    /// Returns number of pieces `player` has of each kind, indexed by [Piece] as `usize`.
+    pub fn all_counts(&self, player: Player) -> [u32; 6] {
+
+        let team = match player {
+            Player::White => &self.white,
+            Player::Black => &self.black,
+        };
+
+        let mut counts = [0; 6];
+        for id in 0..PIECE_COUNT {
+            if team.positions[id] > 0 {
+                counts[team.piece(id) as usize] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Returns true if the king of `player` is on its back rank, with every
+    /// position directly and diagonally in front of it blocked by its own pawns.
+    pub fn back_rank_vulnerable(&self, player: Player) -> bool {
+
+        let (team, back_rank, front_rank) = match player {
+            Player::White => (&self.white, 0, 1),
+            Player::Black => (&self.black, 7, 6),
+        };
+
+        let (x, y) = utils::unflatten_bit(team.positions[index::KING]);
+        if y != back_rank {
+            return false;
+        }
+
+        let escapes = (x.saturating_sub(1)..=(x + 1).min(7))
+            .fold(0, |m, fx| m | utils::flatten_bit(fx, front_rank));
+
+        escapes & !team.pawns() == 0
+    }
+
+    /// Returns the board flipped vertically, with colors swapped and the other
+    /// player to move. Castling availability and en passant are mirrored along.
+    pub fn mirror(&self) -> Board {
+        Board {
+            white: self.black.mirrored(),
+            black: self.white.mirrored(),
+            player: self.player.opponent(),
+            ..*self
+        }
+    }
+
+    /// Sets the player to move. Any en passant capture is no longer available.
+    pub fn set_to_move(&mut self, player: Player) {
+        self.player = player;
+        self.white.en_passant_pos = 0;
+        self.black.en_passant_pos = 0;
+    }
+
+    /// Returns a copy of the board where the current player passes the turn,
+    /// for null-move pruning. No piece moves and en passant is cleared.
+    /// The resulting position is not legal if the current player is in check,
+    /// see [Board::null_move] for a checked version.
+    pub fn make_null_move(&self) -> Board {
+        let mut board = self.clone();
+        board.set_to_move(self.player.opponent());
+        board
+    }
+
+    /// Like [Board::make_null_move], but returns `None` if the current player is in check.
+    pub fn null_move(&self) -> Option<Board> {
+        if self.is_in_check() { None } else { Some(self.make_null_move()) }
+    }
+
+    /// Enables or disables castling for both players. Enabled by default.
+    pub fn set_castling_enabled(&mut self, enabled: bool) {
+        self.castling_disabled = !enabled;
+    }
+
+    pub fn white_iter(&self) -> TeamIterator<'_> {
         TeamIterator::new(&self.white)
     }
 
-    pub fn black_iter(self: &Self) -> TeamIterator {
+    pub fn black_iter(&self) -> TeamIterator<'_> {
         TeamIterator::new(&self.black)
     }
 
-    pub fn has_promotion(self: &Self) -> bool { 
+    /// Returns an iterator over the pieces of both players, white first.
+    pub fn pieces(&self) -> impl Iterator<Item = (Piece, Player, u8, u8)> + '_ {
+        self.white_iter()
+            .map(|(piece, x, y)| (piece, Player::White, x, y))
+            .chain(self.black_iter().map(|(piece, x, y)| (piece, Player::Black, x, y)))
+    }
+
+    /// Returns an iterator over the pieces of both players with their ids, white first.
+    /// The id of a piece stays the same until it is captured, also when promoted.
+    /// White ids are `0..16` and black ids are `16..32`.
+    pub fn pieces_with_ids(&self) -> impl Iterator<Item = (usize, Piece, Player, u8, u8)> + '_ {
+        [(Player::White, &self.white), (Player::Black, &self.black)]
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, (player, team))| (0..PIECE_COUNT)
+                .filter(|&id| team.positions[id] > 0)
+                .map(move |id| {
+                    let (x, y) = utils::unflatten_bit(team.positions[id]);
+                    (i * PIECE_COUNT + id, team.piece(id), player, x, y)
+                }))
+    }
+
+    pub fn has_promotion(&self) -> bool { 
         (match self.player {
             Player::White => self.white.promotion_id,
             Player::Black => self.black.promotion_id,
         }) >= 0
     }
 
-    pub fn is_checkmate(self: &Self) -> bool {
-        
-        // Just check if there are any available moves
-        for id in 0..PIECE_COUNT {
+    /// Returns true if the king of the current player is attacked.
+    pub fn is_in_check(&self) -> bool {
+        self.checkers() > 0
+    }
 
-            if (*match self.player {
-                Player::White => &self.white,
-                Player::Black => &self.black,
-            }).positions[id] == 0 { continue; }
+    /// Returns true if the king of `player` is attacked, regardless of whose turn it is.
+    pub fn is_player_in_check(&self, player: Player) -> bool {
+
+        let (team, opp_team) = match player {
+            Player::White => (&self.white, &self.black, ),
+            Player::Black => (&self.black, &self.white, ),
+        };
+
+        Self::attackers(
+            team.positions[index::KING],
+            team.mask(),
+            opp_team.mask(),
+            &opp_team.positions,
+            &opp_team.promotions,
+            player
+        ) > 0
+    }
 
-            if self.get_legal_moves(id) > 0 {
+    /// Returns true if the current player is in check and has no legal moves.
+    pub fn is_checkmate(&self) -> bool {
+
+        let checkers = self.checkers();
+        if checkers == 0 {
+            return false;
+        }
+
+        // Try escaping with the king first
+        if self.get_legal_moves(index::KING) > 0 {
+            return false;
+        }
+
+        // Only the king can escape a double check
+        if checkers.count_ones() > 1 {
+            return true;
+        }
+
+        // Otherwise the checker must be captured or blocked
+        let team = self.current_team();
+        let kpos = team.positions[index::KING];
+        let mut targets = checkers
+            | utils::ortho_ray_between_excl(kpos, checkers)
+            | utils::diag_ray_between_excl(kpos, checkers);
+
+        // Checking pawn may be captured en passant
+        let opp_team = match self.player {
+            Player::White => &self.black,
+            Player::Black => &self.white,
+        };
+        if opp_team.en_passant_pos == checkers {
+            targets |= self.en_passant_target();
+        }
+
+        for id in 1..PIECE_COUNT {
+            if team.positions[id] > 0 && self.get_legal_moves(id) & targets > 0 {
                 return false;
-            } 
+            }
         }
 
         true
     }
 
-    pub fn select_promotion(self: &mut Self, piece: Piece) {
+    /// Returns true if the current player is not in check but has no legal moves.
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_in_check() && !self.has_legal_moves()
+    }
+
+    /// Returns true if the king of the current player has a legal move,
+    /// regardless of whether other pieces may move.
+    pub fn king_has_moves(&self) -> bool {
+        self.get_legal_moves(index::KING) != 0
+    }
+
+    /// Returns why the game is over, or `None` if it continues.
+    /// Checkmate and stalemate take precedence over insufficient material.
+    pub fn outcome(&self) -> Option<Outcome> {
+        if !self.has_legal_moves() {
+            Some(match self.is_in_check() {
+                true  => Outcome::Checkmate,
+                false => Outcome::Stalemate,
+            })
+        } else if self.is_insufficient_material() {
+            Some(Outcome::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the result if the game is over by checkmate, stalemate or
+    /// insufficient material, or `None` if it continues.
+    pub fn terminal_state(&self) -> Option<GameResult> {
+        self.outcome().map(|outcome| match outcome {
+            Outcome::Checkmate => GameResult::Win(self.player.opponent()),
+            Outcome::Stalemate | Outcome::InsufficientMaterial => GameResult::Draw,
+        })
+    }
+
+    /// Returns true if playing the current player's piece with slot `id` to `mov`
+    /// checkmates the opponent. Pawns reaching the last rank are promoted to
+    /// [Piece::Queen]. The move is assumed to be legal.
+    pub fn is_checkmate_after(&self, id: usize, mov: u64) -> bool {
+        self.after(id, mov).is_checkmate()
+    }
+
+    /// Returns true if playing the current player's piece with slot `id` to `mov`
+    /// stalemates the opponent. Pawns reaching the last rank are promoted to
+    /// [Piece::Queen]. The move is assumed to be legal.
+    pub fn is_stalemate_after(&self, id: usize, mov: u64) -> bool {
+        self.after(id, mov).is_stalemate()
+    }
+
+    // Copy of board with move played
+    fn after(&self, id: usize, mov: u64) -> Board {
+        let mut board = self.clone();
+        board.play_move(id, mov);
+        if board.has_promotion() {
+            board.select_promotion(Piece::Queen);
+        }
+        board
+    }
+
+    /// Returns true if every legal move of the current player is a king move,
+    /// i.e. no other piece can move.
+    pub fn only_king_moves(&self) -> bool {
+
+        if self.is_double_check() {
+            return true;
+        }
+
+        let team = self.current_team();
+
+        !(1..PIECE_COUNT).any(|id| {
+            team.positions[id] > 0 && self.get_legal_moves(id) > 0
+        })
+    }
+
+    fn has_legal_moves(&self) -> bool {
+
+        let team = self.current_team();
+
+        (0..PIECE_COUNT).any(|id| {
+            team.positions[id] > 0 && self.get_legal_moves(id) > 0
+        })
+    }
+
+    pub fn select_promotion(&mut self, piece: Piece) {
 
         let curr = match self.player {
             Player::White => &mut self.white,
@@ -164,7 +729,58 @@ impl Board {
         };
     }
 
-    pub fn play_move(self: &mut Self, id: usize, mov: u64) {
+    /// Returns true if the current player moving to `to` captures a piece, either
+    /// because an opponent piece is there or because it is the en passant target.
+    /// The moving piece is not known, so the en passant target counts for any piece,
+    /// although only pawns capture en passant. See [Board::move_kind] for a given piece.
+    pub fn is_capture(&self, to: u64) -> bool {
+
+        let opp_team = match self.player {
+            Player::White => &self.black,
+            Player::Black => &self.white,
+        };
+
+        opp_team.mask() & to > 0 || self.en_passant_target() & to > 0
+    }
+
+    /// Returns the kind of moving piece with slot `id` of the current player
+    /// to `mov`. The move is assumed to be legal.
+    pub fn move_kind(&self, id: usize, mov: u64) -> MoveKind {
+
+        let (curr_team, opp_team) = match self.player {
+            Player::White => (&self.white, &self.black, ),
+            Player::Black => (&self.black, &self.white, ),
+        };
+
+        let pos = curr_team.positions[id];
+        let capture = opp_team.mask() & mov > 0;
+        let dist = pos.trailing_zeros() as i32 - mov.trailing_zeros() as i32;
+
+        use MoveKind::*;
+
+        match curr_team.piece(id) {
+            Piece::Pawn => {
+                if utils::rank_of(mov.trailing_zeros() as usize) == utils::promotion_rank(self.player) {
+                    if capture { PromotionCapture } else { Promotion }
+                } else if capture {
+                    Capture
+                } else if dist == 16 || dist == -16 {
+                    DoublePush
+                } else if dist == 8 || dist == -8 {
+                    Quiet
+                } else {
+                    // Diagonal move to empty position
+                    EnPassant
+                }
+            },
+            Piece::King if !curr_team.king_moved && curr_team.castling_rook_for(mov).is_some() => {
+                if mov > pos { KingsideCastle } else { QueensideCastle }
+            },
+            _ => if capture { Capture } else { Quiet },
+        }
+    }
+
+    pub fn play_move(&mut self, id: usize, mut mov: u64) {
 
         use Player::*;
 
@@ -173,10 +789,13 @@ impl Board {
             Black => (&mut self.black, &mut self.white, ),
         };
 
+        // promoted pawns don't move like pawns
+        let is_pawn = id >= index::PAWN[0] && curr_team.promotions[id].is_none();
+
         let mut att_pos = mov;
 
         // check en passant attack
-        if id >= index::PAWN[0] && opp_team.en_passant_pos > 0 {
+        if is_pawn && opp_team.en_passant_pos > 0 {
 
              let capt_pos = match self.player {
                  White => mov >> 8,
@@ -188,39 +807,49 @@ impl Board {
              }
         }
 
+        let mut captured = false;
         for p in &mut opp_team.positions[..] {
             if *p == att_pos {
                 *p = 0;
+                captured = true;
                 break;
             }
         }
 
+        // update move counters
+        if captured || is_pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        if matches!(self.player, Black) {
+            self.fullmove_number += 1;
+        }
+
         let pos = curr_team.positions[id];
         let mtz = mov.trailing_zeros() as i32;
 
         let dist = pos.trailing_zeros() as i32 - mtz;
 
         let mut switch = true;
+
+        // en passant is only available directly after the double move
+        curr_team.en_passant_pos = 0;
         
-        if id >= index::PAWN[0] {
+        if is_pawn {
 
             // update en passant pos
             let double_move = dist == 16 || dist == -16;
 
             if double_move {
                 curr_team.en_passant_pos = mov;
-            } else {
-                curr_team.en_passant_pos = 0;
             }
 
             // check for promotion
-            if mtz < 8 || mtz >= 56 {
-                
-                // Can't promote twice
-                if matches!(curr_team.promotions[id], None) {
-                    curr_team.promotion_id = id as isize;
-                    switch = false;
-                }
+            if utils::rank_of(mtz as usize) == utils::promotion_rank(self.player) {
+                curr_team.promotion_id = id as isize;
+                switch = false;
             }
         }
 
@@ -231,38 +860,21 @@ impl Board {
             },
             Piece::King => {
 
-                curr_team.king_moved = true;
-
-                let (castl, cmask, roffset) = match dist {
-                    -2 => ( // left
-                        true,
-                        utils::fill_left_excl(pos),
-                        -1,
-                    ),
-                    2 => ( // right
-                        true,
-                        utils::fill_right_excl(pos),
-                        1,
-                    ),
-                    _ => (false, 0, 0),
+                let castling = match curr_team.king_moved {
+                    false => curr_team.castling_rook_for(mov),
+                    true  => None,
                 };
 
-                if castl {
+                curr_team.king_moved = true;
 
-                    curr_team.did_castling = true;
+                if let Some(rid) = castling {
 
-                    let rpos = match roffset {
-                        -1 => mov >> 1,
-                        1  => mov << 1,
-                        _ => panic!(),
-                    };
+                    curr_team.did_castling = true;
 
-                    for rp in &mut curr_team.positions[index::ROOK[0]..=index::ROOK[1]] {
-                        
-                        if *rp & cmask > 0 {
-                            *rp = rpos;
-                        }
-                    }
+                    let (kdest, rdest) = castling_dests(pos, curr_team.positions[rid]);
+                    curr_team.positions[rid] = rdest;
+                    curr_team.did_move |= rdest;
+                    mov = kdest;
                 }
             }
             _ => (),
@@ -278,23 +890,333 @@ impl Board {
         }
     }
 
-    pub fn get_legal_moves(self: &Self, id: usize) -> u64 {
+    pub fn get_legal_moves(&self, id: usize) -> u64 {
         
         let (curr_team, opp_team) = match self.player {
             Player::White => (&self.white, &self.black, ),
             Player::Black => (&self.black, &self.white, ),
         };
 
+        // No such piece, or it has been captured
+        if id >= PIECE_COUNT || curr_team.positions[id] == 0 {
+            return 0;
+        }
+
+        let pos = curr_team.positions[id];
+        let curr = curr_team.mask();
+        let opp = opp_team.mask();
+        let mut moves = self.unrestricted_moves(id);
+
+        if id == index::KING {
+
+            // The king must not block attacks on positions behind it,
+            // or it could step away from a slider along the checking ray
+            moves = Self::restrict_king(
+                moves,
+                curr & !pos,
+                opp,
+                &opp_team.positions,
+                &opp_team.promotions,
+                self.player
+            );
+
+            moves |= Self::castling_moves(
+                pos,
+                curr_team,
+                opp_team,
+                self.player,
+                self.castling_disabled
+            );
+
+        } else if self.is_double_check() {
+
+            // Only the king can escape a double check
+            moves = 0;
+
+        } else {
+
+            let pins = Self::comp_pins(
+                pos,
+                curr,
+                opp,
+                &opp_team.positions,
+                &opp_team.promotions,
+                curr_team.positions[index::KING],
+                self.player
+            );
+
+            // En passant removes two pawns at once, which pins don't account
+            // for, e.g. when both pawns shield the king from a rook on the same
+            // rank. Verify it by playing the capture instead.
+            let is_pawn = id >= index::PAWN[0] && curr_team.promotions[id].is_none();
+            let ep = if is_pawn { self.en_passant_target() } else { 0 };
+            let ep_legal = moves & ep > 0 && {
+                let mut board = self.clone();
+                board.play_move(id, ep);
+                board.player = self.player;
+                board.checkers() == 0
+            };
+
+            moves = Self::restrict(moves & !ep, pins);
+
+            if ep_legal {
+                moves |= ep;
+            }
+        }
+
+        moves
+    }
+
+    /// Returns the positions the current player's piece at position is restricted to
+    /// because it is pinned to its king, including capturing the pinning piece.
+    /// Returns `None` if there is no such piece, or if it is not pinned.
+    pub fn pin_ray(&self, x: u8, y: u8) -> Option<u64> {
+
+        let id = self.id_from_pos(x, y)?;
+        if id == index::KING {
+            return None;
+        }
+
+        let (curr_team, opp_team) = match self.player {
+            Player::White => (&self.white, &self.black, ),
+            Player::Black => (&self.black, &self.white, ),
+        };
+
+        let pins = |pos| Self::comp_pins(
+            pos,
+            curr_team.mask(),
+            opp_team.mask(),
+            &opp_team.positions,
+            &opp_team.promotions,
+            curr_team.positions[index::KING],
+            self.player
+        );
+
+        // Without a position only checks restrict the piece
+        let restricted = pins(curr_team.positions[id]);
+        if restricted == pins(0) {
+            None
+        } else {
+            Some(restricted)
+        }
+    }
+
+    /// Returns legal moves for piece with slot `id` of `player`, as if it was
+    /// `player`'s turn. Useful for analysing the opponent's options.
+    pub fn legal_moves_for_player(&self, player: Player, id: usize) -> u64 {
+        if player == self.player {
+            self.get_legal_moves(id)
+        } else {
+            let mut b = self.clone();
+            b.player = player;
+            b.get_legal_moves(id)
+        }
+    }
+
+    /// Returns whether the current player may castle kingside or queenside,
+    /// and if not, whether castling is temporarily prevented or no longer possible.
+    pub fn castling_status(&self, kingside: bool) -> CastlingStatus {
+
+        let (curr_team, opp_team) = match self.player {
+            Player::White => (&self.white, &self.black, ),
+            Player::Black => (&self.black, &self.white, ),
+        };
+
+        Self::castling_side_status(curr_team, opp_team, self.player, self.castling_disabled, kingside)
+    }
+
+    /// Returns the (from, to) positions of the rook used for castling kingside
+    /// or queenside by the current player, or `None` if that castling is not
+    /// currently legal.
+    pub fn castling_rook_move(&self, kingside: bool) -> Option<((u8, u8), (u8, u8))> {
+
+        let team = self.current_team();
+        let kpos = team.positions[index::KING];
+        let rpos = team.positions[team.castling_rook(kingside)?];
+
+        if self.get_legal_moves(index::KING) & castling_move(kpos, rpos) == 0 {
+            return None;
+        }
+
+        let (_, rdest) = castling_dests(kpos, rpos);
+        Some((utils::unflatten_bit(rpos), utils::unflatten_bit(rdest)))
+    }
+
+    /// Returns the number of legal moves of the current player, counting each
+    /// pawn move to the last rank once regardless of promotion.
+    pub fn legal_move_count(&self) -> u32 {
+        let team = self.current_team();
+        (0..PIECE_COUNT)
+            .filter(|&id| team.positions[id] > 0)
+            .map(|id| self.get_legal_moves(id).count_ones())
+            .sum()
+    }
+
+    /// Returns the number of legal moves `player` would have if it was
+    /// `player`'s turn, as in [Board::legal_moves_for_player].
+    pub fn mobility(&self, player: Player) -> u32 {
+        if player == self.player {
+            self.legal_move_count()
+        } else {
+            let mut b = self.clone();
+            b.player = player;
+            b.legal_move_count()
+        }
+    }
+
+    /// Returns all legal moves of the current player. Pawn moves to the last rank
+    /// are listed once for each promotion, in the order [Piece::Queen],
+    /// [Piece::Rook], [Piece::Bishop] and [Piece::Knight].
+    pub fn legal_move_list(&self) -> Vec<Move> {
+        self.legal_moves_filtered(|_| true)
+    }
+
+    /// Returns the legal moves of the current player's pieces for which `filter`
+    /// returns true, listed as in [Board::legal_move_list]. Promoted pawns are
+    /// filtered as the piece they were promoted to.
+    pub fn legal_moves_filtered(&self, filter: impl Fn(Piece) -> bool) -> Vec<Move> {
+
+        let team = self.current_team();
+        let mut list = Vec::new();
+
+        for id in 0..PIECE_COUNT {
+
+            let pos = team.positions[id];
+            if pos == 0 || !filter(team.piece(id)) {
+                continue;
+            }
+
+            let from = utils::unflatten_bit(pos);
+
+            for mov in utils::BitIterator::new(self.get_legal_moves(id)) {
+
+                let kind = self.move_kind(id, mov);
+                let mv = Move { kind, ..Move::new(from, utils::unflatten_bit(mov), None) };
+
+                if kind.is_promotion() {
+                    for piece in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+                        list.push(Move { promotion: Some(piece), ..mv });
+                    }
+                } else {
+                    list.push(mv);
+                }
+            }
+        }
+
+        list
+    }
+
+    /// Returns the legal moves of the current player that give check,
+    /// with promotions expanded as in [Board::legal_move_list].
+    pub fn checking_moves(&self) -> Vec<Move> {
+        let opponent = self.player.opponent();
+        self.legal_move_list()
+            .into_iter()
+            .filter(|mv| self.after_move(mv).is_player_in_check(opponent))
+            .collect()
+    }
+
+    /// Returns the legal moves of the current player that capture a piece,
+    /// including en passant and promotions with capture.
+    pub fn capture_moves(&self) -> Vec<Move> {
+        self.legal_move_list()
+            .into_iter()
+            .filter(|mv| mv.kind.is_capture())
+            .collect()
+    }
+
+    /// Returns the legal moves of the current player that don't capture a piece.
+    pub fn quiet_moves(&self) -> Vec<Move> {
+        self.legal_move_list()
+            .into_iter()
+            .filter(|mv| !mv.kind.is_capture())
+            .collect()
+    }
+
+    /// Returns every piece of both players with its position and legal moves,
+    /// current player first. Moves for the opponent are computed as if it was
+    /// the opponent's turn.
+    pub fn full_move_map(&self) -> Vec<MoveMapEntry> {
+
+        let opp_player = match self.player {
+            Player::White => Player::Black,
+            Player::Black => Player::White,
+        };
+
+        let mut map = Vec::new();
+
+        for player in [self.player, opp_player] {
+
+            let team = match player {
+                Player::White => &self.white,
+                Player::Black => &self.black,
+            };
+
+            for id in 0..PIECE_COUNT {
+
+                let pos = team.positions[id];
+                if pos == 0 { continue; }
+
+                let moves = utils::BitIterator::new(self.legal_moves_for_player(player, id))
+                    .map(utils::unflatten_bit)
+                    .collect();
+
+                map.push((team.piece(id), player, utils::unflatten_bit(pos), moves));
+            }
+        }
+
+        map
+    }
+
+    /// Returns pseudo-legal moves for piece with slot `id` of the current player,
+    /// including castling. Moves are not checked for leaving the king in check.
+    /// Returns 0 if there is no piece in slot `id`.
+    pub fn pseudo_legal_moves(&self, id: usize) -> u64 {
+
+        let (curr_team, opp_team) = match self.player {
+            Player::White => (&self.white, &self.black, ),
+            Player::Black => (&self.black, &self.white, ),
+        };
+
+        if id >= PIECE_COUNT || curr_team.positions[id] == 0 {
+            return 0;
+        }
+
+        let pos = curr_team.positions[id];
+        let mut moves = self.unrestricted_moves(id);
+
+        if id == index::KING {
+            moves |= Self::castling_moves(
+                pos,
+                curr_team,
+                opp_team,
+                self.player,
+                self.castling_disabled
+            );
+        }
+
+        moves
+    }
+
+    // Moves of piece with slot id, without regard to check
+    fn unrestricted_moves(&self, id: usize) -> u64 {
+
+        let (curr_team, opp_team) = match self.player {
+            Player::White => (&self.white, &self.black, ),
+            Player::Black => (&self.black, &self.white, ),
+        };
+
         let pos = curr_team.positions[id];
 
         use Piece::*;
         let curr = curr_team.mask();
         let opp = opp_team.mask();
-        let mut moves = match index::into_piece(id) {
+        match index::into_piece(id) {
             // With pawn we must check if it has been promoted first
             Pawn   => if let Some(piece) = curr_team.promotions[id] {
                     // Promotion
                     match piece {
+                        Knight => Self::knight_unrestr(pos, curr, opp),
                         Rook   => Self::ortho_unrestr(pos, curr, opp),
                         Bishop => Self::diag_unrestr(pos, curr, opp),
                         Queen  => Self::ortho_unrestr(pos, curr, opp)
@@ -317,40 +1239,158 @@ impl Board {
             Rook   => Self::ortho_unrestr(pos, curr, opp),
             Queen  => Self::diag_unrestr(pos, curr, opp)
                     | Self::ortho_unrestr(pos, curr, opp),
+        }
+    }
+
+    /// Returns mask of opponent pieces giving check to the current player.
+    pub fn checkers(&self) -> u64 {
+
+        let (curr_team, opp_team) = match self.player {
+            Player::White => (&self.white, &self.black, ),
+            Player::Black => (&self.black, &self.white, ),
+        };
+
+        Self::attackers(
+            curr_team.positions[index::KING],
+            curr_team.mask(),
+            opp_team.mask(),
+            &opp_team.positions,
+            &opp_team.promotions,
+            self.player
+        )
+    }
+
+    /// Returns true if the current player is checked by two pieces at once.
+    pub fn is_double_check(&self) -> bool {
+        self.checkers().count_ones() == 2
+    }
+
+    /// Returns pieces of player `by` that attack position `(x, y)`,
+    /// regardless of whether the attack is a legal move.
+    pub fn attackers_of(&self, x: u8, y: u8, by: Player) -> Vec<(Piece, u8, u8)> {
+
+        use Player::*;
+
+        let (att_team, def_team, def_player) = match by {
+            White => (&self.white, &self.black, Black, ),
+            Black => (&self.black, &self.white, White, ),
+        };
+
+        let att = Self::attackers(
+            utils::flatten_bit(x, y),
+            def_team.mask(),
+            att_team.mask(),
+            &att_team.positions,
+            &att_team.promotions,
+            def_player
+        );
+
+        TeamIterator::new(att_team)
+            .filter(|&(_, x, y)| utils::flatten_bit(x, y) & att > 0)
+            .collect()
+    }
+
+    /// Returns the number of current player pieces attacking `(x, y)`, and the number
+    /// of opponent pieces defending it, as counted by [Board::attackers_of].
+    pub fn attack_defend_count(&self, x: u8, y: u8) -> (u32, u32) {
+        (
+            self.attackers_of(x, y, self.player).len() as u32,
+            self.attackers_of(x, y, self.player.opponent()).len() as u32,
+        )
+    }
+
+    /// Returns true if the current player has a pawn at `(x, y)` with a legal
+    /// move onto the last rank.
+    pub fn pawn_can_promote(&self, x: u8, y: u8) -> bool {
+
+        let id = match self.id_from_pos(x, y) {
+            Some(id) => id,
+            None => return false,
         };
 
-        if id == index::KING {
+        let last_rank = utils::byte_mask(8 * utils::promotion_rank(self.player) as usize);
+
+        self.current_team().piece(id) == Piece::Pawn
+            && self.get_legal_moves(id) & last_rank > 0
+    }
 
-            moves = Self::restrict_king(
-                moves,
-                curr,
-                opp,
-                &opp_team.positions,
-                &opp_team.promotions,
-                self.player
-            );
+    /// Returns the positions strictly between `a` and `b`, ordered from `a` to `b`.
+    /// Empty if the positions are not on the same rank, file or diagonal.
+    pub fn squares_between(a: (u8, u8), b: (u8, u8)) -> Vec<(u8, u8)> {
 
-            moves |= Self::castling_moves(pos, &curr_team, &opp_team, self.player);
+        let (b1, b2) = (utils::flatten_bit(a.0, a.1), utils::flatten_bit(b.0, b.1));
+        if b1 == b2 {
+            return Vec::new();
+        }
 
-        } else {
+        let between = utils::ortho_ray_between_excl(b1, b2) | utils::diag_ray_between_excl(b1, b2);
+        let mut squares = utils::BitIterator::new(between)
+            .map(utils::unflatten_bit)
+            .collect::<Vec<_>>();
 
-            let pins = Self::comp_pins(
-                pos,
-                curr,
-                opp,
-                &opp_team.positions,
-                &opp_team.promotions,
-                curr_team.positions[index::KING],
-                self.player
-            );
+        if b1 > b2 {
+            squares.reverse();
+        }
+        squares
+    }
+
+    /// Returns pieces of the current player that have `(x, y)` among their legal moves.
+    pub fn movers_to(&self, x: u8, y: u8) -> Vec<(Piece, u8, u8)> {
+
+        let dest = utils::flatten_bit(x, y);
+        let team = self.current_team();
+
+        (0..PIECE_COUNT)
+            .filter(|&id| team.positions[id] > 0 && self.get_legal_moves(id) & dest > 0)
+            .map(|id| {
+                let (x, y) = utils::unflatten_bit(team.positions[id]);
+                (team.piece(id), x, y)
+            })
+            .collect()
+    }
+
+    /// Returns mask of all positions attacked by pieces of `player`, including
+    /// positions occupied by its own pieces, regardless of whether the attacks
+    /// are legal moves.
+    pub fn attack_map(&self, player: Player) -> u64 {
+
+        let team = match player {
+            Player::White => &self.white,
+            Player::Black => &self.black,
+        };
+
+        // Every piece is treated as a blocker that may be attacked
+        let occ = self.occupied();
+        let forward = |pos| match player {
+            Player::White => utils::fill_left_excl(pos),
+            Player::Black => utils::fill_right_excl(pos),
+        };
 
-            moves = Self::restrict(moves, pins);
+        let mut map = 0;
+
+        for id in 0..PIECE_COUNT {
+
+            let pos = team.positions[id];
+            if pos == 0 {
+                continue;
+            }
+
+            use Piece::*;
+            map |= match team.piece(id) {
+                Pawn   => MOVES.pawn_attacks[pos.trailing_zeros() as usize] & forward(pos),
+                Knight => Self::knight_unrestr(pos, 0, occ),
+                King   => Self::king_unrestr(pos, 0, occ),
+                Bishop => Self::diag_unrestr(pos, 0, occ),
+                Rook   => Self::ortho_unrestr(pos, 0, occ),
+                Queen  => Self::diag_unrestr(pos, 0, occ)
+                        | Self::ortho_unrestr(pos, 0, occ),
+            };
         }
 
-        moves
+        map
     }
 
-    pub fn id_from_pos(self: &Self, x: u8, y: u8) -> Option<usize> {
+    pub fn id_from_pos(&self, x: u8, y: u8) -> Option<usize> {
 
         let b = utils::flatten_bit(x, y);
         let ps = &match self.player {
@@ -365,6 +1405,45 @@ impl Board {
         None
     }
 
+    fn current_team(&self) -> &Team {
+        match self.player {
+            Player::White => &self.white,
+            Player::Black => &self.black,
+        }
+    }
+
+    // Square behind a pawn that just made a double move,
+    // if the current player has a pawn that may capture it
+    fn en_passant_target(&self) -> u64 {
+
+        let (curr_team, opp_team) = match self.player {
+            Player::White => (&self.white, &self.black, ),
+            Player::Black => (&self.black, &self.white, ),
+        };
+
+        let ep = opp_team.en_passant_pos;
+        if ep == 0 {
+            return 0;
+        }
+
+        let mut pawns = 0;
+        for id in index::PAWN {
+            if curr_team.promotions[id].is_none() {
+                pawns |= curr_team.positions[id];
+            }
+        }
+
+        let adjacent = (ep << 1 | ep >> 1) & utils::byte_mask(ep.trailing_zeros() as usize);
+        if adjacent & pawns == 0 {
+            return 0;
+        }
+
+        match self.player {
+            Player::White => ep << 8,
+            Player::Black => ep >> 8,
+        }
+    }
+
     fn ortho_unrestr(pos: u64, curr: u64, opp: u64) -> u64 {
 
         debug_assert!(pos > 0); 
@@ -538,57 +1617,67 @@ impl Board {
         kpos: u64,
         curr_team: &Team,
         opp_team: &Team,
-        player: Player
+        player: Player,
+        disabled: bool
     ) -> u64 {
 
         let mut moves = 0;
 
-        if curr_team.king_moved {
-            return 0;
-        }
-
-        // Get available rooks
-        let mut rooks = 0;
-        for &p in &curr_team.positions[index::ROOK[0]..=index::ROOK[1]] {
-            rooks |= p;
+        for kingside in [true, false] {
+            let status = Self::castling_side_status(curr_team, opp_team, player, disabled, kingside);
+            if status == CastlingStatus::Available {
+                // Rook is present if castling is available
+                let rid = curr_team.castling_rook(kingside).unwrap();
+                moves |= castling_move(kpos, curr_team.positions[rid]);
+            }
         }
-        
-        // Only rooks on the same row
-        rooks &= utils::byte_mask(kpos.trailing_zeros() as usize);
 
-        // Only rooks that haven't moved
-        rooks &= !curr_team.did_move;
-
-        let move_mask = kpos << 2 | kpos >> 2;
+        moves
+    }
 
-        for r in utils::BitIterator::new(rooks) {
+    fn castling_side_status(
+        curr_team: &Team,
+        opp_team: &Team,
+        player: Player,
+        disabled: bool,
+        kingside: bool
+    ) -> CastlingStatus {
 
-            // Make sure positions are vacant
-            let between = utils::ortho_ray_between_excl(r, kpos);
-            if between & (curr_team.mask() | opp_team.mask()) > 0 {
-                continue;
-            }
+        if disabled || curr_team.king_moved {
+            return CastlingStatus::Lost;
+        }
 
-            // Find move destination
-            let mov = move_mask & between;
+        let kpos = curr_team.positions[index::KING];
+        let rpos = match curr_team.castling_rook(kingside) {
+            Some(rid) => curr_team.positions[rid],
+            None => return CastlingStatus::Lost,
+        };
 
-            // Make sure no square on the way is attacked
-            for b in utils::BitIterator::new(utils::ortho_ray_between_excl(kpos, mov)) {
+        let occupied = curr_team.mask() | opp_team.mask();
+        let (kdest, rdest) = castling_dests(kpos, rpos);
+        let king_path = utils::fill_between_incl(kpos, kdest);
 
-                if !Self::is_attacked(
-                    b,
-                    curr_team.mask(),
-                    opp_team.mask(),
-                    &opp_team.positions,
-                    &opp_team.promotions,
-                    player
-                ) {
-                    moves |= mov;
-                }
-            }
+        // Make sure positions are vacant, apart from the king and rook
+        let path = king_path | utils::fill_between_incl(rpos, rdest);
+        if path & occupied & !(kpos | rpos) > 0 {
+            return CastlingStatus::Blocked;
         }
 
-        moves
+        // Make sure king is not in check, and no square on the way is attacked
+        let attacked = utils::BitIterator::new(king_path).any(|b| Self::is_attacked(
+            b,
+            curr_team.mask() & !(kpos | rpos),
+            opp_team.mask(),
+            &opp_team.positions,
+            &opp_team.promotions,
+            player
+        ));
+
+        if attacked {
+            CastlingStatus::ThroughCheck
+        } else {
+            CastlingStatus::Available
+        }
     }
 
     fn ortho_can_reach(pos: u64, target: u64, blk: u64) -> bool {
@@ -667,7 +1756,7 @@ impl Board {
         
         for i in PAWN[0]..=PAWN[7] {
             // May be promoted
-            if !matches!(opp_prom[i], None) {
+            if opp_prom[i].is_some() {
                 continue;
             }
             let p = &opp_pos[i];
@@ -680,9 +1769,18 @@ impl Board {
         if kn_poses & (opp_pos[KNIGHT[0]] | opp_pos[KNIGHT[1]]) > 0 {
             return true;
         }
-        
+
+        // Kings may not be adjacent
+        if MOVES.king_moves[id] & opp_pos[KING] > 0 {
+            return true;
+        }
+
         // Promoted pawns
         for i in PAWN[0]..=PAWN[7] {
+            // Captured promoted pieces keep their promotion
+            if opp_pos[i] == 0 {
+                continue;
+            }
             if let Some(Piece::Knight) = opp_prom[i] {
                 let tz = opp_pos[i].trailing_zeros() as usize;
                 let pkn_poses = MOVES.knight_moves[tz];
@@ -718,31 +1816,80 @@ impl Board {
 
                 let p = opp_pos[i];
 
-                if matches!(piece, Piece::Rook) || matches!(piece, Piece::Queen) {
-
-                    if Self::ortho_can_reach(p, pos, (curr & !pos) | opp) {
-                        if p == pos {
-                            // We can capture it
-                            continue;
-                        }
-                        return true;
+                if (matches!(piece, Piece::Rook) || matches!(piece, Piece::Queen))
+                    && Self::ortho_can_reach(p, pos, (curr & !pos) | opp)
+                {
+                    if p == pos {
+                        // We can capture it
+                        continue;
                     }
+                    return true;
                 }
 
-                if matches!(piece, Piece::Bishop) || matches!(piece, Piece::Queen) {
-
-                    if Self::diag_can_reach(p, pos, (curr & !pos) | opp) {
-                        if p == pos {
-                            // We can capture it
-                            continue;
-                        }
-                        return true;
+                if (matches!(piece, Piece::Bishop) || matches!(piece, Piece::Queen))
+                    && Self::diag_can_reach(p, pos, (curr & !pos) | opp)
+                {
+                    if p == pos {
+                        // We can capture it
+                        continue;
                     }
+                    return true;
                 }
             }
         }
 
-        return false;
+        false
+    }
+
+    // Same as is_attacked, but returns mask of all attackers
+    fn attackers(
+        pos: u64,
+        curr: u64,
+        opp: u64,
+        opp_pos: &[u64],
+        opp_prom: &[Option<Piece>],
+        player: Player
+    ) -> u64 {
+
+        use Player::*;
+
+        let id = pos.trailing_zeros() as usize;
+        let blk = (curr & !pos) | opp;
+        let mut att = 0;
+
+        let pwn_att = MOVES.pawn_attacks[id]
+            & match player {
+                White => utils::fill_left_excl(pos),
+                Black => utils::fill_right_excl(pos),
+            };
+
+        let kn_poses = MOVES.knight_moves[id];
+
+        for (i, &p) in opp_pos.iter().enumerate() {
+
+            if p == 0 || p == pos { continue; }
+
+            let piece = match opp_prom[i] {
+                None => index::into_piece(i),
+                Some(piece) => piece,
+            };
+
+            let reach = match piece {
+                Piece::Pawn   => p & pwn_att > 0,
+                Piece::Knight => p & kn_poses > 0,
+                Piece::Rook   => Self::ortho_can_reach(p, pos, blk),
+                Piece::Bishop => Self::diag_can_reach(p, pos, blk),
+                Piece::Queen  => Self::ortho_can_reach(p, pos, blk)
+                                || Self::diag_can_reach(p, pos, blk),
+                Piece::King   => p & MOVES.king_moves[id] > 0,
+            };
+
+            if reach {
+                att |= p;
+            }
+        }
+
+        att
     }
 
     fn comp_pins(
@@ -767,7 +1914,7 @@ impl Board {
 
         for i in PAWN[0]..=PAWN[7] {
             // May be promoted
-            if !matches!(opp_prom[i], None) {
+            if opp_prom[i].is_some() {
                 continue;
             }
             let p = opp_pos[i];
@@ -840,16 +1987,17 @@ impl Board {
                             if ray == 0 {
                                 // It might be adjacent, in which case ray is empty
                                 // Thus we check inclusive ray
+                                // Queens must still be checked diagonally, so don't continue
                                 if utils::ortho_ray_between_incl(king_pos, p) > 0 {
                                     pins &= p;
                                 }
-                                continue;
-                            }
-                            let blockers = (ray & (curr | opp)).count_ones();
-                            if blockers == 0 || // Not blocked, must be blocked pr captured
-                                blockers == 1 && ray & pos > 0 // Only blocker, must stay in lane pr capture
-                            {
-                                pins &= ray | p;
+                            } else {
+                                let blockers = (ray & (curr | opp)).count_ones();
+                                if blockers == 0 || // Not blocked, must be blocked or captured
+                                    blockers == 1 && ray & pos > 0 // Only blocker, must stay in lane or capture
+                                {
+                                    pins &= ray | p;
+                                }
                             }
                         }
 
@@ -919,3 +2067,750 @@ impl<'a> Iterator for TeamIterator<'a> {
         } else { None }
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use crate::board::*;
+
+    #[test]
+    fn promoted_knight_moves() {
+        // Third knight doesn't fit in the knight slots and is stored as a promoted pawn
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/NNN1K3 w - - 0 1").unwrap();
+        let id = board.id_from_pos(2, 0).unwrap();
+        assert!(id >= index::PAWN[0]);
+
+        let moves = utils::flatten_bit(0, 1)
+                  | utils::flatten_bit(1, 2)
+                  | utils::flatten_bit(3, 2)
+                  | utils::flatten_bit(4, 1);
+        assert_eq!(board.get_legal_moves(id), moves);
+    }
+
+    #[test]
+    fn occupied() {
+        let board = Board::new();
+        assert_eq!(board.occupied().count_ones(), 32);
+        assert_eq!(utils::BitIterator::new(board.occupied()).count(), 32);
+        assert_eq!(board.occupied_by(Player::White), 0xffff);
+        assert_eq!(board.occupied_by(Player::Black), 0xffff << 48);
+    }
+
+    #[test]
+    fn full_move_map() {
+        let map = Board::new().full_move_map();
+        assert_eq!(map.len(), 32);
+
+        let white = map.iter().filter(|e| e.1 == Player::White).collect::<Vec<_>>();
+        let black = map.iter().filter(|e| e.1 == Player::Black).collect::<Vec<_>>();
+        assert_eq!(white.len(), 16);
+        assert_eq!(white.iter().map(|e| e.3.len()).sum::<usize>(), 20);
+        assert_eq!(black.iter().map(|e| e.3.len()).sum::<usize>(), 20);
+
+        let knight = map.iter().find(|e| e.2 == (1, 7)).unwrap();
+        assert_eq!(knight.0, Piece::Knight);
+        assert_eq!(knight.3, vec![(0, 5), (2, 5)]);
+    }
+
+    #[test]
+    fn pseudo_legal() {
+        // Rook on e2 is pinned by rook on e8
+        let board = Board::from_fen("4r2k/8/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+        let id = board.id_from_pos(4, 1).unwrap();
+        let legal = board.get_legal_moves(id);
+        let pseudo = board.pseudo_legal_moves(id);
+        assert!(pseudo.count_ones() > legal.count_ones());
+        assert_eq!(pseudo & legal, legal);
+        assert_eq!(legal, utils::col_mask(4) & !utils::flatten_bit(4, 0) & !utils::flatten_bit(4, 1));
+
+        // Empty and out of range slots have no moves
+        for id in [index::QUEEN, PIECE_COUNT] {
+            assert_eq!(board.get_legal_moves(id), 0);
+            assert_eq!(board.pseudo_legal_moves(id), 0);
+        }
+    }
+
+    #[test]
+    fn validate() {
+        assert!(Board::new().validate().is_ok());
+
+        // Black is in check with white to move
+        assert_eq!(
+            Board::from_fen("4k3/4R3/8/8/8/8/8/4K3 w - - 0 1").err(),
+            Some(Error::IllegalPosition)
+        );
+        assert!(Board::from_fen("4k3/4R3/8/8/8/8/8/4K3 b - - 0 1").is_ok());
+
+        // Pawn on last rank
+        assert!(Board::from_fen("3Pk3/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        // Missing king
+        assert!(Board::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        // Two kings
+        assert!(Board::from_fen("3kk3/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+
+        let mut squares = [[None; 8]; 8];
+        squares[0][4] = Some((Piece::King, Player::White));
+        squares[7][4] = Some((Piece::King, Player::Black));
+        squares[6][4] = Some((Piece::Queen, Player::White));
+        assert!(Board::from_array(&squares, Player::Black).is_ok());
+        assert_eq!(Board::from_array(&squares, Player::White).err(), Some(Error::IllegalPosition));
+    }
+
+    #[test]
+    fn attack_defend_count() {
+        let board = Board::from_fen("4k3/8/4p3/3n4/4P3/1B6/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.attack_defend_count(3, 4), (2, 1));
+        assert_eq!(board.attack_defend_count(4, 5), (0, 0));
+        assert_eq!(board.attack_defend_count(3, 6), (0, 1));
+
+        let board = Board::from_fen("4k3/8/4p3/3n4/4P3/1B6/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(board.attack_defend_count(3, 4), (1, 2));
+    }
+
+    #[test]
+    fn attackers_of() {
+        let board = Board::from_fen("4k3/8/8/8/4P3/1B6/8/4K3 w - - 0 1").unwrap();
+
+        let att = board.attackers_of(3, 4, Player::White);
+        assert_eq!(att.len(), 2);
+        assert!(att.contains(&(Piece::Pawn, 4, 3)));
+        assert!(att.contains(&(Piece::Bishop, 1, 2)));
+
+        assert_eq!(board.attackers_of(6, 5, Player::White), vec![]);
+        assert_eq!(board.attackers_of(3, 6, Player::Black), vec![(Piece::King, 4, 7)]);
+    }
+
+    #[test]
+    fn captured_promotion() {
+        // The black knight promoted on b1 is captured, but keeps its promotion
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/1p6/R3K3 b - - 0 1").unwrap();
+        let id = board.id_from_pos(1, 1).unwrap();
+        board.play_move(id, utils::flatten_bit(1, 0));
+        board.select_promotion(Piece::Knight);
+        let id = board.id_from_pos(0, 0).unwrap();
+        board.play_move(id, utils::flatten_bit(1, 0));
+        board.play_move(index::KING, utils::flatten_bit(3, 7));
+
+        let mut count = 0;
+        for id in 0..PIECE_COUNT {
+            if board.white.positions[id] > 0 {
+                count += board.get_legal_moves(id).count_ones();
+            }
+        }
+        assert_eq!(count, 15);
+    }
+
+    #[test]
+    fn is_legal_position() {
+        assert!(Board::new().is_legal_position());
+
+        // Opponent in check
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4QK2 b - - 0 1").unwrap();
+        assert!(board.is_legal_position());
+        board.set_to_move(Player::White);
+        assert!(!board.is_legal_position());
+
+        // Adjacent kings
+        let mut board = Board::from_fen("8/8/8/8/8/3k4/8/4K3 w - - 0 1").unwrap();
+        board.black.positions[index::KING] = utils::flatten_bit(4, 1);
+        assert!(!board.is_legal_position());
+
+        // Missing king
+        let mut board = Board::from_fen("8/8/8/8/8/3k4/8/4K3 w - - 0 1").unwrap();
+        board.black.positions[index::KING] = 0;
+        assert!(!board.is_legal_position());
+    }
+
+    #[test]
+    fn king_has_moves() {
+        assert!(!Board::new().king_has_moves());
+
+        // King boxed in by its own pawns and a bishop, but the pawns may move
+        let board = Board::from_fen("4k3/8/8/8/8/4b3/6PP/7K w - - 0 1").unwrap();
+        assert!(!board.king_has_moves());
+        assert!(board.has_legal_moves());
+        assert!(!board.is_stalemate());
+
+        let board = Board::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!board.king_has_moves());
+        assert!(board.is_stalemate());
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(board.king_has_moves());
+    }
+
+    #[test]
+    fn pawn_can_promote() {
+        let board = Board::from_fen("1n2k3/P1P4P/8/8/8/8/7p/4K3 w - - 0 1").unwrap();
+        assert!(board.pawn_can_promote(0, 6));
+        assert!(board.pawn_can_promote(2, 6));
+        assert!(board.pawn_can_promote(7, 6));
+        assert!(!board.pawn_can_promote(7, 1));
+        assert!(!board.pawn_can_promote(4, 0));
+        assert!(!board.pawn_can_promote(3, 3));
+
+        // Blocked, and pinned
+        let board = Board::from_fen("k3r3/4P3/8/8/8/8/p7/4K3 w - - 0 1").unwrap();
+        assert!(!board.pawn_can_promote(4, 6));
+        let board = Board::from_fen("8/KP5r/8/4k3/8/8/8/8 w - - 0 1").unwrap();
+        assert!(!board.pawn_can_promote(1, 6));
+    }
+
+    #[test]
+    fn squares_between() {
+        assert_eq!(Board::squares_between((0, 0), (0, 3)), vec![(0, 1), (0, 2)]);
+        assert_eq!(Board::squares_between((7, 4), (4, 4)), vec![(6, 4), (5, 4)]);
+        assert_eq!(Board::squares_between((1, 1), (4, 4)), vec![(2, 2), (3, 3)]);
+        assert_eq!(Board::squares_between((5, 2), (2, 5)), vec![(4, 3), (3, 4)]);
+
+        // Adjacent, same or not aligned
+        assert_eq!(Board::squares_between((3, 3), (4, 4)), vec![]);
+        assert_eq!(Board::squares_between((3, 3), (3, 3)), vec![]);
+        assert_eq!(Board::squares_between((0, 0), (1, 2)), vec![]);
+        assert_eq!(Board::squares_between((0, 0), (7, 1)), vec![]);
+    }
+
+    #[test]
+    fn movers_to() {
+        let board = Board::from_fen("4k3/8/8/8/8/2N5/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(board.movers_to(3, 4), vec![(Piece::Knight, 2, 2), (Piece::Queen, 3, 0)]);
+        assert_eq!(board.movers_to(4, 3), vec![(Piece::Knight, 2, 2)]);
+        assert_eq!(board.movers_to(7, 7), vec![]);
+
+        // Pinned knight can't move
+        let board = Board::from_fen("4k3/8/8/b7/8/2N5/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(board.movers_to(3, 4), vec![(Piece::Queen, 3, 0)]);
+    }
+
+    #[test]
+    fn castling_rook_move() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/R7/4K2R w K - 0 1").unwrap();
+        assert_eq!(board.castling_rook_move(true), Some(((7, 0), (5, 0))));
+        assert_eq!(board.castling_rook_move(false), None);
+
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/4K3 b kq - 0 1").unwrap();
+        assert_eq!(board.castling_rook_move(false), Some(((0, 7), (3, 7))));
+        assert_eq!(board.castling_rook_move(true), Some(((7, 7), (5, 7))));
+
+        // f8 is attacked
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/4KR2 b kq - 0 1").unwrap();
+        assert_eq!(board.castling_rook_move(true), None);
+    }
+
+    #[test]
+    fn legal_moves_filtered() {
+        let board = Board::new();
+        let knights = board.legal_moves_filtered(|piece| piece == Piece::Knight);
+        assert_eq!(knights.len(), 4);
+        assert!(knights.iter().all(|mv| mv.from == (1, 0) || mv.from == (6, 0)));
+        assert_eq!(board.legal_moves_filtered(|piece| piece == Piece::Queen), vec![]);
+        assert_eq!(board.legal_moves_filtered(|_| true), board.legal_move_list());
+
+        // Second queen is in a pawn slot
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/QQ2K3 w - - 0 1").unwrap();
+        let queens = board.legal_moves_filtered(|piece| piece == Piece::Queen);
+        let kings = board.legal_moves_filtered(|piece| piece == Piece::King);
+        assert!(queens.iter().all(|mv| mv.from == (0, 0) || mv.from == (1, 0)));
+        assert!(queens.iter().any(|mv| mv.from == (0, 0)));
+        assert_eq!(queens.len() + kings.len(), board.legal_move_count() as usize);
+    }
+
+    #[test]
+    fn pieces_with_ids() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2N w - - 0 1").unwrap();
+        let pieces = board.pieces_with_ids().collect::<Vec<_>>();
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[0], (index::KING, Piece::King, Player::White, 4, 0));
+        assert_eq!(pieces[2], (PIECE_COUNT + index::KING, Piece::King, Player::Black, 4, 7));
+        assert_eq!(
+            pieces.iter().map(|&(_, piece, player, x, y)| (piece, player, x, y)).collect::<Vec<_>>(),
+            board.pieces().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn castling_status() {
+        use CastlingStatus::*;
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K2R w K - 0 1").unwrap();
+        assert_eq!(board.castling_status(true), Available);
+        assert_eq!(board.castling_status(false), Lost);
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/RN2K1NR w KQ - 0 1").unwrap();
+        assert_eq!(board.castling_status(true), Blocked);
+        assert_eq!(board.castling_status(false), Blocked);
+
+        // f1 is attacked
+        let board = Board::from_fen("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(board.castling_status(true), ThroughCheck);
+        assert_eq!(board.castling_status(false), Available);
+
+        // b1 may be attacked when castling queenside, as the king doesn't pass it
+        let board = Board::from_fen("1r2k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(board.castling_status(true), Available);
+        assert_eq!(board.castling_status(false), Available);
+
+        // In check
+        let board = Board::from_fen("4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(board.castling_status(true), ThroughCheck);
+        assert_eq!(board.castling_status(false), ThroughCheck);
+
+        let mut board = Board::new();
+        board.set_castling_enabled(false);
+        assert_eq!(board.castling_status(true), Lost);
+        board.set_to_move(Player::Black);
+        board.set_castling_enabled(true);
+        assert_eq!(board.castling_status(false), Blocked);
+    }
+
+    #[test]
+    fn checkmate() {
+        let mates = [
+            // Fool's mate
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+            // Smothered mate
+            "6rk/5Npp/8/8/8/8/8/6K1 b - - 0 1",
+            // Double check
+            "4k3/8/8/8/8/5n2/3PPP2/r3KB2 w - - 0 1",
+            // Back rank mate
+            "3R3k/6pp/8/8/8/8/8/6K1 b - - 0 1",
+        ];
+        for fen in mates {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(board.is_in_check(), "{}", fen);
+            assert!(board.is_checkmate(), "{}", fen);
+            assert!(!board.is_stalemate(), "{}", fen);
+            assert!(!board.has_legal_moves(), "{}", fen);
+        }
+
+        let not_mates = [
+            // Rook can block
+            "3R3k/6pp/8/8/8/8/4r3/6K1 b - - 0 1",
+            // Knight can capture the checker
+            "3R3k/6pp/4n3/8/8/8/8/6K1 b - - 0 1",
+            // Checking pawn can be captured en passant
+            "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1",
+            // Not in check
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        ];
+        for fen in not_mates {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(!board.is_checkmate(), "{}", fen);
+            assert!(board.has_legal_moves(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn stalemate() {
+        let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!board.is_in_check());
+        assert!(!board.is_checkmate());
+        assert!(board.is_stalemate());
+    }
+
+    #[test]
+    fn king_checking_ray() {
+        // King on g8 can't escape the rook by moving to h8
+        let board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(board.get_legal_moves(index::KING), 0);
+        assert!(board.is_checkmate());
+    }
+
+    #[test]
+    fn only_king_moves() {
+        assert!(!Board::new().only_king_moves());
+
+        // Double check by knight and rook
+        let board = Board::from_fen("4k3/8/8/8/8/5n2/3P4/r3K3 w - - 0 1").unwrap();
+        assert!(board.is_double_check());
+        assert!(board.only_king_moves());
+
+        // Pawn is blocked
+        let board = Board::from_fen("4k3/8/8/8/8/4p3/4P3/4K3 w - - 0 1").unwrap();
+        assert!(board.only_king_moves());
+    }
+
+    #[test]
+    fn pin_ray() {
+        let board = Board::from_fen("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+        let ray = (1..7).fold(0, |ray, y| ray | utils::flatten_bit(4, y));
+        assert_eq!(board.pin_ray(4, 1), Some(ray));
+        assert_eq!(board.pin_ray(4, 0), None);
+        assert_eq!(board.pin_ray(3, 3), None);
+
+        // Not pinned
+        let board = Board::from_fen("4k3/4r3/8/8/8/8/3R4/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.pin_ray(3, 1), None);
+
+        // Pinned diagonally by a promoted queen
+        let board = Board::from_fen("4k2q/8/8/8/1q6/8/3B4/4K3 w - - 0 1").unwrap();
+        let ray = utils::flatten_bit(1, 3) | utils::flatten_bit(2, 2) | utils::flatten_bit(3, 1);
+        assert_eq!(board.pin_ray(3, 1), Some(ray));
+        assert_eq!(board.get_legal_moves(board.id_from_pos(3, 1).unwrap()), ray & !utils::flatten_bit(3, 1));
+    }
+
+    #[test]
+    fn en_passant_pin() {
+        // Capturing would expose the king to the rook along the rank
+        let board = Board::from_fen("8/8/8/K2Pp2r/8/8/8/7k w - e6 0 1").unwrap();
+        let id = board.id_from_pos(3, 4).unwrap();
+        assert_eq!(board.get_legal_moves(id), utils::flatten_bit(3, 5));
+
+        let board = Board::from_fen("8/8/8/8/k2pP2Q/8/8/7K b - e3 0 1").unwrap();
+        let id = board.id_from_pos(3, 3).unwrap();
+        assert_eq!(board.get_legal_moves(id), utils::flatten_bit(3, 2));
+
+        // Another piece on the rank blocks the rook
+        let board = Board::from_fen("8/8/8/K2Pp1Nr/8/8/8/7k w - e6 0 1").unwrap();
+        let id = board.id_from_pos(3, 4).unwrap();
+        assert_eq!(board.get_legal_moves(id), utils::flatten_bit(3, 5) | utils::flatten_bit(4, 5));
+
+        // Capturing the checking pawn en passant resolves the check
+        let board = Board::from_fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        let id = board.id_from_pos(4, 3).unwrap();
+        assert_eq!(board.get_legal_moves(id), utils::flatten_bit(3, 2));
+
+        // Only pawns capture en passant, other pieces may still move to the target
+        let board = Board::from_fen("4k3/8/8/2pP4/1N6/8/8/4K3 w - c6 0 1").unwrap();
+        let id = board.id_from_pos(1, 3).unwrap();
+        assert!(board.get_legal_moves(id) & utils::flatten_bit(2, 5) > 0);
+    }
+
+    #[test]
+    fn piece_count() {
+        let board = Board::new();
+        assert_eq!(board.all_counts(Player::White), [8, 2, 2, 2, 1, 1]);
+        assert_eq!(board.piece_count(Player::White, Piece::Pawn), 8);
+        assert_eq!(board.piece_count(Player::White, Piece::Knight), 2);
+        assert_eq!(board.piece_count(Player::Black, Piece::Queen), 1);
+
+        // Promoted pawn counts as a queen
+        let board = Board::from_fen("3qk3/8/8/8/8/8/8/1Q1QK3 w - - 0 1").unwrap();
+        assert_eq!(board.all_counts(Player::White), [0, 0, 0, 0, 2, 1]);
+        assert_eq!(board.all_counts(Player::Black), [0, 0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn mate_after() {
+        let board = Board::from_fen("7k/6pp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let rook = board.id_from_pos(0, 0).unwrap();
+        assert!(board.is_checkmate_after(rook, utils::flatten_bit(0, 7)));
+        assert!(!board.is_checkmate_after(rook, utils::flatten_bit(0, 6)));
+        assert!(!board.is_stalemate_after(rook, utils::flatten_bit(0, 7)));
+
+        let board = Board::from_fen("7k/8/6K1/8/8/8/8/5Q2 w - - 0 1").unwrap();
+        let queen = board.id_from_pos(5, 0).unwrap();
+        assert!(board.is_stalemate_after(queen, utils::flatten_bit(5, 6)));
+        assert!(!board.is_checkmate_after(queen, utils::flatten_bit(5, 6)));
+
+        // Promotion to queen mates
+        let board = Board::from_fen("7k/P5pp/8/8/8/8/8/6K1 w - - 0 1").unwrap();
+        let pawn = board.id_from_pos(0, 6).unwrap();
+        assert!(board.is_checkmate_after(pawn, utils::flatten_bit(0, 7)));
+    }
+
+    #[test]
+    fn chess960_castling() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/RK5R w KQ - 0 1").unwrap();
+        let moves = board.get_legal_moves(index::KING);
+        // Kingside castling moves king to g1, queenside is selected by the rook
+        // since c1 is a regular king move
+        assert!(moves & utils::flatten_bit(6, 0) > 0);
+        assert!(moves & utils::flatten_bit(0, 0) > 0);
+        assert_eq!(board.castling_rook_move(true), Some(((7, 0), (5, 0))));
+        assert_eq!(board.castling_rook_move(false), Some(((0, 0), (3, 0))));
+
+        let mut kingside = board.clone();
+        assert_eq!(kingside.move_kind(index::KING, utils::flatten_bit(6, 0)), MoveKind::KingsideCastle);
+        kingside.play_move(index::KING, utils::flatten_bit(6, 0));
+        assert_eq!(kingside.to_fen(), "4k3/8/8/8/8/8/8/R4RK1 b - - 1 1");
+
+        let mut queenside = board.clone();
+        assert_eq!(queenside.move_kind(index::KING, utils::flatten_bit(0, 0)), MoveKind::QueensideCastle);
+        queenside.play_move(index::KING, utils::flatten_bit(0, 0));
+        assert_eq!(queenside.to_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+
+        // Regular king move to c1
+        let mut regular = board.clone();
+        assert_eq!(regular.move_kind(index::KING, utils::flatten_bit(2, 0)), MoveKind::Quiet);
+        regular.play_move(index::KING, utils::flatten_bit(2, 0));
+        assert_eq!(regular.to_fen(), "4k3/8/8/8/8/8/8/R1K4R b - - 1 1");
+
+        // Rook on d8 attacks d1 which the king doesn't pass, while e8 attacks e1 which it does
+        let board = Board::from_fen("3r3k/7p/8/8/8/8/8/RK5R w KQ - 0 1").unwrap();
+        assert_eq!(board.castling_rook_move(false), Some(((0, 0), (3, 0))));
+        let board = Board::from_fen("4r2k/7p/8/8/8/8/8/RK5R w KQ - 0 1").unwrap();
+        assert_eq!(board.castling_rook_move(true), None);
+
+        // Squares the rook passes must be vacant
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/RKN4R w KQ - 0 1").unwrap();
+        assert_eq!(board.castling_rook_move(false), None);
+    }
+
+    #[test]
+    fn standard_castling() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let moves = board.get_legal_moves(index::KING);
+        assert!(moves & utils::flatten_bit(6, 0) > 0);
+        assert!(moves & utils::flatten_bit(2, 0) > 0);
+        assert!(moves & (utils::flatten_bit(0, 0) | utils::flatten_bit(7, 0)) == 0);
+
+        // Can't castle out of check
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K1rR w KQkq - 0 1").unwrap();
+        assert!(board.get_legal_moves(index::KING) & utils::flatten_bit(2, 0) == 0);
+    }
+
+    #[test]
+    fn is_capture() {
+        let board = Board::from_fen("4k3/8/8/3pP3/8/2p5/3P4/4K3 w - d6 0 1").unwrap();
+        assert!(board.is_capture(utils::flatten_bit(2, 2)));
+        assert!(board.is_capture(utils::flatten_bit(3, 5)));
+        assert!(!board.is_capture(utils::flatten_bit(3, 2)));
+        assert!(!board.is_capture(utils::flatten_bit(4, 5)));
+    }
+
+    #[test]
+    fn legal_move_list() {
+        let list = Board::new().legal_move_list();
+        assert_eq!(list.len(), 20);
+        assert_eq!(Board::new().legal_move_count(), 20);
+        assert!(list.iter().all(|mv| mv.promotion.is_none()));
+        assert!(list.contains(&Move::new((4, 1), (4, 3), None)));
+
+        // Push and capture on the last rank, and a king with a single move
+        let board = Board::from_fen("1r5k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let list = board.legal_move_list();
+        assert_eq!(list.len(), 8 + 1);
+        let promotions = list.iter()
+            .filter(|mv| mv.to == (1, 7))
+            .map(|mv| mv.promotion)
+            .collect::<Vec<_>>();
+        assert_eq!(promotions, [Some(Piece::Queen), Some(Piece::Rook), Some(Piece::Bishop), Some(Piece::Knight)]);
+        assert!(list.iter().filter(|mv| mv.to == (1, 7)).all(|mv| mv.kind == MoveKind::PromotionCapture));
+    }
+
+    #[test]
+    fn capture_moves() {
+        assert!(Board::new().capture_moves().is_empty());
+        assert_eq!(Board::new().quiet_moves().len(), 20);
+
+        let board = Board::from_fen("1r2k3/P7/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let captures = board.capture_moves();
+        let to = captures.iter().map(|mv| (mv.to, mv.promotion)).collect::<Vec<_>>();
+        assert_eq!(captures.len(), 5);
+        assert!(to.contains(&((3, 5), None)));
+        assert!(to.contains(&((1, 7), Some(Piece::Knight))));
+        assert!(captures.iter().all(|mv| mv.to != (4, 5) && mv.to != (0, 7)));
+
+        let quiet = board.quiet_moves();
+        assert_eq!(quiet.len() + captures.len(), board.legal_move_list().len());
+        assert!(quiet.iter().any(|mv| mv.to == (4, 5)));
+    }
+
+    #[test]
+    fn insufficient_material() {
+        assert!(!Board::new().is_insufficient_material());
+
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2N1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1",
+        ] {
+            assert!(Board::from_fen(fen).unwrap().is_insufficient_material(), "{}", fen);
+        }
+
+        for fen in [
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3RK3 w - - 0 1",
+            "3bk3/8/8/8/8/8/8/3BK3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/2N1K3 w - - 0 1",
+        ] {
+            assert!(!Board::from_fen(fen).unwrap().is_insufficient_material(), "{}", fen);
+        }
+
+        // Pawn promoted to bishop is a bishop, not a pawn
+        let mut board = Board::from_fen("7k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        board.play_move(board.id_from_pos(0, 6).unwrap(), utils::flatten_bit(0, 7));
+        board.select_promotion(Piece::Bishop);
+        assert!(board.is_insufficient_material());
+
+        // Promoted bishop on a light position and black bishop on a dark position
+        let mut board = Board::from_fen("7k/P7/8/8/8/8/8/K1b5 w - - 0 1").unwrap();
+        board.play_move(board.id_from_pos(0, 6).unwrap(), utils::flatten_bit(0, 7));
+        board.select_promotion(Piece::Bishop);
+        assert!(!board.is_insufficient_material());
+    }
+
+    #[test]
+    fn is_player_in_check() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+        assert!(board.is_player_in_check(Player::White));
+        assert!(!board.is_player_in_check(Player::Black));
+
+        board.player = Player::Black;
+        assert!(board.is_player_in_check(Player::White));
+        assert!(!board.is_player_in_check(Player::Black));
+        assert!(!board.is_in_check());
+    }
+
+    #[test]
+    fn back_rank_vulnerable() {
+        let mut board = Board::new();
+        assert!(board.back_rank_vulnerable(Player::White));
+        assert!(board.back_rank_vulnerable(Player::Black));
+
+        board.play_move(board.id_from_pos(4, 1).unwrap(), utils::flatten_bit(4, 3)); // e4
+        assert!(!board.back_rank_vulnerable(Player::White));
+        assert!(board.back_rank_vulnerable(Player::Black));
+
+        // King in the corner only needs two pawns, but a piece doesn't count
+        let board = Board::from_fen("6rk/6pp/8/8/8/8/6PB/7K w - - 0 1").unwrap();
+        assert!(board.back_rank_vulnerable(Player::Black));
+        assert!(!board.back_rank_vulnerable(Player::White));
+
+        // King off the back rank
+        let board = Board::from_fen("8/6pp/7k/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert!(!board.back_rank_vulnerable(Player::Black));
+        assert!(board.back_rank_vulnerable(Player::White));
+    }
+
+    #[test]
+    fn set_to_move() {
+        let mut board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        board.set_to_move(Player::Black);
+        assert_eq!(board.player, Player::Black);
+        board.set_to_move(Player::White);
+        assert_eq!(board.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn null_move() {
+        let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 5 30").unwrap();
+        let null = board.null_move().unwrap();
+        assert_eq!(null.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 b - - 5 30");
+        assert_eq!(null.make_null_move().player, Player::White);
+
+        // Not allowed when in check
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        assert!(board.null_move().is_none());
+    }
+
+    #[test]
+    fn mirror() {
+        let board = Board::from_fen("r3k3/8/8/3pP3/8/8/8/4K2R w Kq d6 3 20").unwrap();
+        let mirrored = board.mirror();
+        assert_eq!(mirrored.to_fen(), "4k2r/8/8/8/3Pp3/8/8/R3K3 b Qk d3 3 20");
+        assert_eq!(mirrored.mirror().to_fen(), board.to_fen());
+        assert_eq!(mirrored.legal_move_count(), board.legal_move_count());
+
+        let board = Board::from_fen("r1bqk2r/pppp1ppp/2n2n2/4p3/1bB1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 4 5").unwrap();
+        let mirrored = board.mirror();
+        assert_eq!(mirrored.mirror().to_fen(), board.to_fen());
+        assert_eq!(mirrored.material_balance(), -board.material_balance());
+        assert_eq!(mirrored.positional_score(), board.positional_score());
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/QQ2K3 w - - 0 1").unwrap();
+        assert_eq!(board.material_balance(), 1800);
+        assert_eq!(board.mirror().material_balance(), -1800);
+    }
+
+    #[test]
+    fn attack_map() {
+        // Ranks 2 and 3, and b1 to g1
+        let board = Board::new();
+        assert_eq!(board.attack_map(Player::White), 0x00_00_00_00_00_ff_ff_7e);
+        assert_eq!(board.attack_map(Player::Black), 0x7e_ff_ff_00_00_00_00_00);
+
+        // Rook attacks the blocking pawn, but not beyond
+        let board = Board::from_fen("4k3/8/8/8/r2P4/8/8/4K3 w - - 0 1").unwrap();
+        let map = board.attack_map(Player::Black);
+        assert!(map & utils::flatten_bit(3, 3) > 0);
+        assert!(map & utils::flatten_bit(4, 3) == 0);
+        assert!(map & utils::flatten_bit(0, 0) > 0);
+        assert!(map & utils::flatten_bit(3, 7) > 0);
+    }
+
+    #[test]
+    fn terminal_state() {
+        assert_eq!(Board::new().terminal_state(), None);
+
+        let board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(board.terminal_state(), Some(GameResult::Win(Player::White)));
+
+        let board = Board::from_fen("7k/5Q2/7K/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(board.terminal_state(), Some(GameResult::Draw));
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/3NK3 w - - 0 1").unwrap();
+        assert_eq!(board.terminal_state(), Some(GameResult::Draw));
+        assert_eq!(board.outcome(), Some(Outcome::InsufficientMaterial));
+
+        // Stalemate with insufficient material
+        let board = Board::from_fen("k7/8/1K6/4B3/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(board.outcome(), Some(Outcome::Stalemate));
+    }
+
+    #[test]
+    fn checking_moves() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let moves = board.checking_moves();
+        assert_eq!(moves.len(), 1);
+        assert_eq!((moves[0].from, moves[0].to), ((0, 0), (0, 7)));
+
+        // Only queen and rook promotions give check
+        let board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotions = board.checking_moves()
+            .into_iter()
+            .map(|mv| mv.promotion)
+            .collect::<Vec<_>>();
+        assert_eq!(promotions, [Some(Piece::Queen), Some(Piece::Rook)]);
+
+        assert!(Board::new().checking_moves().is_empty());
+    }
+
+    #[test]
+    fn mobility() {
+        let board = Board::new();
+        assert_eq!(board.mobility(Player::White), 20);
+        assert_eq!(board.mobility(Player::Black), 20);
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert_eq!(board.mobility(Player::White), 15);
+        assert_eq!(board.mobility(Player::Black), 5);
+    }
+
+    #[test]
+    fn king_adjacency() {
+        // Kings may never be adjacent, also when no other piece guards the square
+        let board = Board::from_fen("7k/8/6K1/8/8/8/8/5Q2 w - - 0 1").unwrap();
+        let moves = board.get_legal_moves(index::KING);
+        assert_eq!(moves & (utils::flatten_bit(6, 6) | utils::flatten_bit(7, 6)), 0);
+        assert_eq!(moves.count_ones(), 6);
+
+        let board = Board::from_fen("8/8/8/3k4/8/3K4/8/8 b - - 0 1").unwrap();
+        let moves = board.get_legal_moves(index::KING);
+        assert_eq!(moves & utils::byte_mask(utils::flatten(0, 3)), 0);
+        assert_eq!(moves.count_ones(), 5);
+    }
+
+    #[test]
+    fn double_check() {
+        // Rook on e8 and knight on d3 both check the white king
+        let board = Board::from_fen("4r2k/8/8/8/8/3n4/R7/4KB2 w - - 0 1").unwrap();
+        assert!(board.is_double_check());
+
+        // Bishop could capture the knight and rook could block the rook,
+        // but neither resolves both checks
+        for id in 1..PIECE_COUNT {
+            if board.white.positions[id] > 0 {
+                assert_eq!(board.get_legal_moves(id), 0);
+            }
+        }
+        assert!(board.get_legal_moves(index::KING) > 0);
+
+        let board = Board::from_fen("4r2k/8/8/8/8/8/R7/4KB2 w - - 0 1").unwrap();
+        assert!(!board.is_double_check());
+        assert_eq!(board.checkers().count_ones(), 1);
+    }
+}
@@ -0,0 +1,537 @@
+
+use super::{ Board, Team, index, };
+use crate::error::Error;
+use crate::piece::Piece;
+use crate::player::Player;
+use crate::utils;
+
+/// Castling availability of both players.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+impl Board {
+
+    /// Returns the position in Forsyth-Edwards Notation.
+    pub fn to_fen(&self) -> String {
+
+        let mut fen = self.placement_fen();
+
+        fen.push_str(match self.player {
+            Player::White => " w ",
+            Player::Black => " b ",
+        });
+
+        fen.push_str(&self.castling_rights_str());
+
+        // En passant target, only if it may be captured
+        fen.push(' ');
+        match self.en_passant_target() {
+            0 => fen.push('-'),
+            t => {
+                let (x, y) = utils::unflatten_bit(t);
+                fen.push_str(&utils::square_name(x, y));
+            },
+        };
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+
+        fen
+    }
+
+    /// Returns the piece placement field of Forsyth-Edwards Notation,
+    /// e.g. `"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"`.
+    pub fn placement_fen(&self) -> String {
+
+        let mut squares = [None; 64];
+        for (piece, x, y) in self.white_iter() {
+            squares[utils::flatten(x, y)] = Some(piece_char(piece).to_ascii_uppercase());
+        }
+        for (piece, x, y) in self.black_iter() {
+            squares[utils::flatten(x, y)] = Some(piece_char(piece));
+        }
+
+        let mut fen = String::new();
+
+        // Piece placement, from rank 8 to rank 1
+        for y in (0..8).rev() {
+            let mut empty = 0;
+            for x in 0..8 {
+                match squares[utils::flatten(x, y)] {
+                    None => empty += 1,
+                    Some(c) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(c);
+                    },
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen
+    }
+
+    /// Returns castling availability as in Forsyth-Edwards Notation,
+    /// e.g. `"KQkq"`, or `"-"` if neither player may castle.
+    pub fn castling_rights_str(&self) -> String {
+
+        let rights = self.castling_rights();
+
+        let mut castling = String::new();
+        if rights.white_kingside  { castling.push('K'); }
+        if rights.white_queenside { castling.push('Q'); }
+        if rights.black_kingside  { castling.push('k'); }
+        if rights.black_queenside { castling.push('q'); }
+
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        castling
+    }
+
+    /// Sets castling availability, e.g. for a board created with [Board::from_array].
+    /// A right is only available if the king and a rook on that side of it are on
+    /// the back rank. Castling must also be enabled, see [Board::set_castling_enabled].
+    pub fn set_castling_rights(&mut self, rights: CastlingRights) {
+        self.white.set_castling_rights(Player::White, rights.white_kingside, rights.white_queenside);
+        self.black.set_castling_rights(Player::Black, rights.black_kingside, rights.black_queenside);
+    }
+
+    /// Returns castling availability, i.e. whether the king and the rook
+    /// on each side have not moved. All are false if castling is disabled.
+    pub fn castling_rights(&self) -> CastlingRights {
+
+        if self.castling_disabled {
+            return CastlingRights::default();
+        }
+
+        let (white_kingside, white_queenside) = self.white.castling_rights();
+        let (black_kingside, black_queenside) = self.black.castling_rights();
+
+        CastlingRights {
+            white_kingside,
+            white_queenside,
+            black_kingside,
+            black_queenside,
+        }
+    }
+
+    /// Returns a key identifying the position for detecting repetitions,
+    /// made up of piece placement, player to move, castling availability and
+    /// en passant target. The en passant target is only included if capturing
+    /// en passant is legal.
+    pub fn position_key(&self) -> String {
+
+        let fen = self.to_fen();
+        let fields = fen.split(' ').collect::<Vec<_>>();
+
+        format!(
+            "{} {} {} {}",
+            fields[0],
+            fields[1],
+            fields[2],
+            if self.legal_en_passant_target() > 0 { fields[3] } else { "-" }
+        )
+    }
+
+    // En passant target if capturing en passant is legal, otherwise 0
+    pub(super) fn legal_en_passant_target(&self) -> u64 {
+
+        let team = self.current_team();
+        let target = self.en_passant_target();
+        let legal = target > 0 && index::PAWN.iter().any(|&id| {
+            team.promotions[id].is_none()
+                && team.positions[id] > 0
+                && self.get_legal_moves(id) & target > 0
+        });
+
+        if legal { target } else { 0 }
+    }
+
+    /// Creates a board from a position in Forsyth-Edwards Notation.
+    /// The move counters may be omitted.
+    /// Returns [Error::InvalidFen] if the string is malformed or grants castling
+    /// without the king and rook on the back rank, and
+    /// [Error::IllegalPosition] if the position is not legal.
+    pub fn from_fen(fen: &str) -> Result<Board, Error> {
+
+        let mut fields = fen.split_whitespace();
+
+        let placement = fields.next().ok_or(Error::InvalidFen)?;
+        let player = match fields.next() {
+            Some("w") => Player::White,
+            Some("b") => Player::Black,
+            _ => return Err(Error::InvalidFen),
+        };
+        let castling = fields.next().ok_or(Error::InvalidFen)?;
+        let en_passant = fields.next().ok_or(Error::InvalidFen)?;
+        let halfmove_clock = match fields.next() {
+            None => 0,
+            Some(s) => s.parse().map_err(|_| Error::InvalidFen)?,
+        };
+        let fullmove_number = match fields.next() {
+            None => 1,
+            Some(s) => s.parse().map_err(|_| Error::InvalidFen)?,
+        };
+
+        if fields.next().is_some() {
+            return Err(Error::InvalidFen);
+        }
+
+        let mut board = Board {
+            player,
+            halfmove_clock,
+            fullmove_number,
+            ..Default::default()
+        };
+
+        // Piece placement, from rank 8 to rank 1
+        let ranks = placement.split('/').collect::<Vec<_>>();
+        if ranks.len() != 8 {
+            return Err(Error::InvalidFen);
+        }
+
+        let mut white = Vec::new();
+        let mut black = Vec::new();
+
+        for (i, rank) in ranks.iter().enumerate() {
+            let y = 7 - i as u8;
+            let mut x = 0;
+            for c in rank.chars() {
+                if let Some(d) = c.to_digit(10) {
+                    if d == 0 || d > 8 {
+                        return Err(Error::InvalidFen);
+                    }
+                    x += d as u8;
+                } else {
+                    if x >= 8 {
+                        return Err(Error::InvalidFen);
+                    }
+                    let piece = char_piece(c).ok_or(Error::InvalidFen)?;
+                    let bit = utils::flatten_bit(x, y);
+                    if c.is_ascii_uppercase() {
+                        white.push((piece, bit));
+                    } else {
+                        black.push((piece, bit));
+                    }
+                    x += 1;
+                }
+            }
+            if x != 8 {
+                return Err(Error::InvalidFen);
+            }
+        }
+
+        board.white.place(&white)?;
+        board.black.place(&black)?;
+
+        // Castling availability
+        let (mut wk, mut wq, mut bk, mut bq) = (false, false, false, false);
+        if castling != "-" {
+            for c in castling.chars() {
+                match c {
+                    'K' => wk = true,
+                    'Q' => wq = true,
+                    'k' => bk = true,
+                    'q' => bq = true,
+                    _ => return Err(Error::InvalidFen),
+                }
+            }
+        }
+        board.white.set_castling_rights(Player::White, wk, wq);
+        board.black.set_castling_rights(Player::Black, bk, bq);
+
+        // Every right needs its king and rook on the back rank
+        let rights = board.castling_rights();
+        if (rights.white_kingside, rights.white_queenside, rights.black_kingside, rights.black_queenside)
+            != (wk, wq, bk, bq)
+        {
+            return Err(Error::InvalidFen);
+        }
+
+        // En passant target, stored as position of the pawn to capture
+        if en_passant != "-" {
+
+            let (x, y) = utils::parse_square(en_passant).ok_or(Error::InvalidFen)?;
+            let (opp_team, target_y, pawn_y) = match player {
+                Player::White => (&mut board.black, 5, 4),
+                Player::Black => (&mut board.white, 2, 3),
+            };
+
+            let pawn = utils::flatten_bit(x, pawn_y);
+            let has_pawn = index::PAWN.iter().any(|&id| {
+                opp_team.positions[id] == pawn && opp_team.promotions[id].is_none()
+            });
+
+            if y != target_y || !has_pawn {
+                return Err(Error::InvalidFen);
+            }
+
+            opp_team.en_passant_pos = pawn;
+        }
+
+        board.validate()?;
+
+        Ok(board)
+    }
+}
+
+impl Team {
+
+    // Marks rooks as moved, or king if no castling is available
+    fn set_castling_rights(&mut self, player: Player, kingside: bool, queenside: bool) {
+
+        let kpos = self.positions[index::KING];
+
+        // Castling is only possible from the back rank
+        let back_rank = match player {
+            Player::White => utils::byte_mask(0),
+            Player::Black => utils::byte_mask(56),
+        };
+        let (kingside, queenside) = if kpos & back_rank > 0 {
+            (kingside, queenside)
+        } else {
+            (false, false)
+        };
+
+        let mut rooks = 0;
+        for &p in &self.positions[index::ROOK[0]..=index::ROOK[1]] {
+            rooks |= p;
+        }
+        rooks &= utils::byte_mask(kpos.trailing_zeros() as usize);
+
+        // Forget previous rights
+        self.king_moved = false;
+        self.did_move &= !rooks;
+
+        if !kingside && !queenside {
+            self.king_moved = true;
+            return;
+        }
+
+        if !kingside {
+            self.did_move |= rooks & utils::fill_left_excl(kpos);
+        }
+        if !queenside {
+            self.did_move |= rooks & utils::fill_right_excl(kpos);
+        }
+    }
+
+    // Returns (kingside, queenside) castling availability
+    pub(super) fn castling_rights(&self) -> (bool, bool) {
+
+        if self.king_moved {
+            return (false, false);
+        }
+
+        let kpos = self.positions[index::KING];
+
+        let mut rooks = 0;
+        for &p in &self.positions[index::ROOK[0]..=index::ROOK[1]] {
+            rooks |= p;
+        }
+        rooks &= utils::byte_mask(kpos.trailing_zeros() as usize);
+        rooks &= !self.did_move;
+
+        (
+            rooks & utils::fill_left_excl(kpos) > 0,
+            rooks & utils::fill_right_excl(kpos) > 0,
+        )
+    }
+}
+
+fn char_piece(c: char) -> Option<Piece> {
+    match c.to_ascii_lowercase() {
+        'p' => Some(Piece::Pawn),
+        'r' => Some(Piece::Rook),
+        'n' => Some(Piece::Knight),
+        'b' => Some(Piece::Bishop),
+        'q' => Some(Piece::Queen),
+        'k' => Some(Piece::King),
+        _   => None,
+    }
+}
+
+fn piece_char(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn   => 'p',
+        Piece::Rook   => 'r',
+        Piece::Knight => 'n',
+        Piece::Bishop => 'b',
+        Piece::Queen  => 'q',
+        Piece::King   => 'k',
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::board::*;
+
+    #[test]
+    fn position_key() {
+        // Pawn on d4 is pinned by the bishop and can't capture en passant
+        let with = Board::from_fen("8/8/5k2/8/3pP3/8/1B6/4K3 b - e3 0 1").unwrap();
+        let without = Board::from_fen("8/8/5k2/8/3pP3/8/1B6/4K3 b - - 0 1").unwrap();
+        assert_ne!(with.to_fen(), without.to_fen());
+        assert_eq!(with.position_key(), without.position_key());
+        assert_eq!(with.position_key(), "8/8/5k2/8/3pP3/8/1B6/4K3 b - -");
+
+        // Without the bishop it can
+        let with = Board::from_fen("8/8/5k2/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let without = Board::from_fen("8/8/5k2/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(with.position_key(), without.position_key());
+    }
+
+    #[test]
+    fn placement_fen() {
+        assert_eq!(Board::new().placement_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+
+        let fen = "r3k3/8/8/3pP3/8/8/8/4K2R w Kq d6 3 20";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.placement_fen(), "r3k3/8/8/3pP3/8/8/8/4K2R");
+        assert!(fen.starts_with(&board.placement_fen()));
+    }
+
+    #[test]
+    fn move_counters() {
+        let fen = "4k3/4p3/8/8/8/8/8/R3K3 b Q - 24 40";
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (24, 40));
+
+        board.play_move(board.id_from_pos(4, 7).unwrap(), utils::flatten_bit(3, 7)); // Kd8
+        assert!(board.to_fen().ends_with(" 25 41"));
+        board.play_move(board.id_from_pos(0, 0).unwrap(), utils::flatten_bit(0, 3)); // Ra4
+        assert!(board.to_fen().ends_with(" 26 41"));
+        board.play_move(board.id_from_pos(4, 6).unwrap(), utils::flatten_bit(4, 4)); // e5
+        assert!(board.to_fen().ends_with(" 0 42"));
+
+        // Counters default when omitted
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w -  -").unwrap();
+        assert!(board.to_fen().ends_with(" 0 1"));
+    }
+
+    #[test]
+    fn castling_rights_str() {
+        let mut board = Board::new();
+        assert_eq!(board.castling_rights_str(), "KQkq");
+
+        board.play_move(board.id_from_pos(7, 1).unwrap(), utils::flatten_bit(7, 3)); // h4
+        board.play_move(board.id_from_pos(0, 6).unwrap(), utils::flatten_bit(0, 4)); // a5
+        board.play_move(board.id_from_pos(7, 0).unwrap(), utils::flatten_bit(7, 2)); // Rh3
+        assert_eq!(board.castling_rights_str(), "Qkq");
+
+        board.play_move(board.id_from_pos(0, 7).unwrap(), utils::flatten_bit(0, 5)); // Ra6
+        assert_eq!(board.castling_rights_str(), "Qk");
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.castling_rights_str(), "-");
+
+        // Rights need the king and a rook on the back rank
+        assert!(Board::from_fen("4k3/8/8/8/4K2R/8/8/8 w K - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/7R/4K3 w K - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").is_err());
+    }
+
+    #[test]
+    fn castling_rights() {
+        let mut board = Board::new();
+        assert_eq!(board.castling_rights(), CastlingRights {
+            white_kingside: true,
+            white_queenside: true,
+            black_kingside: true,
+            black_queenside: true,
+        });
+
+        board.play_move(board.id_from_pos(0, 1).unwrap(), utils::flatten_bit(0, 3)); // a4
+        board.play_move(board.id_from_pos(7, 6).unwrap(), utils::flatten_bit(7, 4)); // h5
+        board.play_move(board.id_from_pos(0, 0).unwrap(), utils::flatten_bit(0, 2)); // Ra3
+        assert_eq!(board.castling_rights(), CastlingRights {
+            white_kingside: true,
+            white_queenside: false,
+            black_kingside: true,
+            black_queenside: true,
+        });
+
+        board.set_castling_enabled(false);
+        assert_eq!(board.castling_rights(), CastlingRights::default());
+    }
+
+    #[test]
+    fn set_castling_rights() {
+        let mut squares = [[None; 8]; 8];
+        squares[0][0] = Some((Piece::Rook, Player::White));
+        squares[0][4] = Some((Piece::King, Player::White));
+        squares[0][7] = Some((Piece::Rook, Player::White));
+        squares[7][4] = Some((Piece::King, Player::Black));
+        squares[7][7] = Some((Piece::Rook, Player::Black));
+
+        let mut board = Board::from_array(&squares, Player::White).unwrap();
+        assert_eq!(board.castling_rights(), CastlingRights::default());
+
+        board.set_castling_rights(CastlingRights { white_queenside: true, ..Default::default() });
+        assert_eq!(board.castling_rights_str(), "Q");
+        let castling = board.legal_move_list()
+            .into_iter()
+            .filter(|mv| matches!(mv.kind, MoveKind::KingsideCastle | MoveKind::QueensideCastle))
+            .collect::<Vec<_>>();
+        assert_eq!(castling.len(), 1);
+        assert_eq!(castling[0].kind, MoveKind::QueensideCastle);
+
+        // No rook on the queenside for black
+        board.set_castling_rights(CastlingRights {
+            white_kingside: true,
+            black_kingside: true,
+            black_queenside: true,
+            ..Default::default()
+        });
+        assert_eq!(board.castling_rights_str(), "Kk");
+
+        board.set_castling_rights(CastlingRights::default());
+        assert_eq!(board.castling_rights_str(), "-");
+
+        // Black king and rook are not on the back rank
+        let mut board = Board::from_fen("8/4k2r/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+        board.set_castling_rights(CastlingRights {
+            white_kingside: true,
+            black_kingside: true,
+            ..Default::default()
+        });
+        assert_eq!(board.castling_rights_str(), "K");
+    }
+
+    #[test]
+    fn en_passant() {
+        // No black pawn can capture after 1.e4
+        let mut board = Board::new();
+        board.play_move(board.id_from_pos(4, 1).unwrap(), utils::flatten_bit(4, 3));
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+
+        // Black pawn on d4 may capture e4
+        let mut board = Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3p4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 3").unwrap();
+        board.play_move(board.id_from_pos(4, 1).unwrap(), utils::flatten_bit(4, 3));
+        let fen = board.to_fen();
+        assert_eq!(fen, "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3");
+
+        let imported = Board::from_fen(&fen).unwrap();
+        assert_eq!(imported.white.en_passant_pos, board.white.en_passant_pos);
+        assert_eq!(imported.to_fen(), fen);
+
+        // Target must be behind a pawn of the player that just moved
+        assert!(Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e6 0 3").is_err());
+        assert!(Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq d3 0 3").is_err());
+    }
+}
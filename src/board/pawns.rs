@@ -0,0 +1,127 @@
+
+use super::Board;
+use crate::moves::MOVES;
+use crate::player::Player;
+use crate::utils;
+
+/// Counts of pawn structure weaknesses and strengths, for evaluation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PawnStructure {
+    /// Pawns on a file already occupied by another pawn of the same player,
+    /// i.e. two pawns on one file count as one doubled pawn.
+    pub doubled: u32,
+    /// Pawns with no pawns of the same player on adjacent files.
+    pub isolated: u32,
+    /// Pawns with no opponent pawns ahead of them on the same or adjacent files.
+    pub passed: u32,
+}
+
+impl Board {
+
+    /// Returns the pawn structure of `player`. Promoted pawns are not counted.
+    pub fn pawn_structure(&self, player: Player) -> PawnStructure {
+
+        let (pawns, opp_pawns) = match player {
+            Player::White => (self.white.pawns(), self.black.pawns()),
+            Player::Black => (self.black.pawns(), self.white.pawns()),
+        };
+
+        let mut structure = PawnStructure::default();
+
+        for x in 0..8 {
+            let count = (pawns & utils::col_mask(x)).count_ones();
+            structure.doubled += count.saturating_sub(1);
+        }
+
+        for pawn in utils::BitIterator::new(pawns) {
+
+            let (x, y) = utils::unflatten_bit(pawn);
+            let x = x as usize;
+
+            let mut adjacent = 0;
+            if x > 0 { adjacent |= utils::col_mask(x - 1); }
+            if x < 7 { adjacent |= utils::col_mask(x + 1); }
+
+            if pawns & adjacent == 0 {
+                structure.isolated += 1;
+            }
+
+            // Ranks in front of the pawn, as seen by player
+            let ahead = match player {
+                Player::White => utils::fill_left_excl(utils::flatten_bit(7, y)),
+                Player::Black => utils::fill_right_excl(utils::flatten_bit(0, y)),
+            };
+
+            if opp_pawns & ahead & (adjacent | utils::col_mask(x)) == 0 {
+                structure.passed += 1;
+            }
+        }
+
+        structure
+    }
+
+    /// Returns true if a pawn of `by` attacks `(x, y)`, regardless of whether
+    /// the position is occupied. Promoted pawns are not counted.
+    pub fn pawn_defended(&self, x: u8, y: u8, by: Player) -> bool {
+
+        let pos = utils::flatten_bit(x, y);
+
+        // Positions a pawn of by attacks pos from, behind it as seen by by
+        let (pawns, behind) = match by {
+            Player::White => (self.white.pawns(), utils::fill_right_excl(pos)),
+            Player::Black => (self.black.pawns(), utils::fill_left_excl(pos)),
+        };
+
+        MOVES.pawn_attacks[utils::flatten(x, y)] & behind & pawns > 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::board::*;
+
+    #[test]
+    fn pawn_structure() {
+        let board = Board::new();
+        assert_eq!(board.pawn_structure(Player::White), PawnStructure::default());
+
+        // White: doubled c-pawns, isolated and passed h-pawn
+        // Black: isolated b- and e-pawns
+        let board = Board::from_fen("4k3/8/1p2p3/8/2PP3P/2P5/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.pawn_structure(Player::White), PawnStructure {
+            doubled: 1,
+            isolated: 1,
+            passed: 1,
+        });
+        assert_eq!(board.pawn_structure(Player::Black), PawnStructure {
+            doubled: 0,
+            isolated: 2,
+            passed: 0,
+        });
+    }
+
+    #[test]
+    fn pawn_defended() {
+        let board = Board::from_fen("4k3/8/8/3p4/8/2P5/8/4K3 w - - 0 1").unwrap();
+
+        // Diagonally in front of the c3 pawn
+        assert!(board.pawn_defended(1, 3, Player::White));
+        assert!(board.pawn_defended(3, 3, Player::White));
+        assert!(!board.pawn_defended(2, 3, Player::White));
+        assert!(!board.pawn_defended(1, 1, Player::White));
+
+        // Defended by the d5 pawn, also when occupied
+        assert!(board.pawn_defended(2, 3, Player::Black));
+        assert!(board.pawn_defended(4, 3, Player::Black));
+        assert!(!board.pawn_defended(2, 5, Player::Black));
+        assert!(!board.pawn_defended(1, 3, Player::Black));
+
+        // Pawns on the edge
+        let board = Board::from_fen("4k3/7p/8/8/8/8/P7/4K3 w - - 0 1").unwrap();
+        assert!(board.pawn_defended(1, 2, Player::White));
+        assert!(!board.pawn_defended(7, 2, Player::White));
+        assert!(board.pawn_defended(6, 5, Player::Black));
+        assert!(!board.pawn_defended(0, 5, Player::Black));
+    }
+}
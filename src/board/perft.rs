@@ -0,0 +1,79 @@
+
+use super::Board;
+use crate::chess_move::Move;
+use crate::piece::Piece;
+
+impl Board {
+
+    /// Returns the number of leaf positions reached by playing all legal move
+    /// sequences of length `depth`. Used for verifying move generation.
+    pub fn perft(&self, depth: u32) -> u64 {
+
+        match depth {
+            0 => 1,
+            1 => self.legal_move_list().len() as u64,
+            _ => self.legal_move_list()
+                .iter()
+                .map(|mv| self.after_move(mv).perft(depth - 1))
+                .sum(),
+        }
+    }
+
+    /// Returns each legal move paired with the [Board::perft] count of the
+    /// position after it, at `depth - 1`. Useful for locating move generation bugs.
+    pub fn perft_divide(&self, depth: u32) -> Vec<(Move, u64)> {
+
+        if depth == 0 {
+            return Vec::new();
+        }
+
+        self.legal_move_list()
+            .into_iter()
+            .map(|mv| (mv, self.after_move(&mv).perft(depth - 1)))
+            .collect()
+    }
+
+    // Copy of board with move played, promoting to queen if none is given
+    pub(crate) fn after_move(&self, mv: &Move) -> Board {
+
+        let mut board = self.clone();
+        let id = board.id_from_pos(mv.from.0, mv.from.1).unwrap();
+        board.play_move(id, crate::utils::flatten_bit(mv.to.0, mv.to.1));
+
+        if board.has_promotion() {
+            board.select_promotion(mv.promotion.unwrap_or(Piece::Queen));
+        }
+
+        board
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::board::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn perft() {
+        let board = Board::new();
+        assert_eq!(board.perft(0), 1);
+        assert_eq!(board.perft(1), 20);
+        assert_eq!(board.perft(2), 400);
+        assert_eq!(board.perft(3), 8902);
+
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        assert_eq!(board.perft(1), 48);
+        assert_eq!(board.perft(2), 2039);
+    }
+
+    #[test]
+    fn perft_divide() {
+        for board in [Board::new(), Board::from_fen(KIWIPETE).unwrap()] {
+            let divide = board.perft_divide(3);
+            assert_eq!(divide.len() as u64, board.perft(1));
+            assert_eq!(divide.iter().map(|&(_, n)| n).sum::<u64>(), board.perft(3));
+        }
+    }
+}
@@ -0,0 +1,165 @@
+
+use super::{ Board, PIECE_COUNT, };
+use crate::chess_move::{ Move, MoveKind, };
+use crate::piece::Piece;
+use crate::utils;
+
+impl Board {
+
+    /// Returns a legal move in Standard Algebraic Notation, e.g. `"Nbd7"`, `"exd6"`,
+    /// `"e8=Q+"` or `"O-O"`. Promotes to [Piece::Queen] if [Move::promotion] is `None`.
+    pub fn san(&self, mv: &Move) -> String {
+
+        // Moves are legal, so there is a piece to move
+        let id = self.id_from_pos(mv.from.0, mv.from.1).unwrap();
+        let to = utils::flatten_bit(mv.to.0, mv.to.1);
+        let kind = self.move_kind(id, to);
+
+        let team = self.current_team();
+        let piece = team.piece(id);
+
+        let mut san = String::new();
+
+        match kind {
+            MoveKind::KingsideCastle => san.push_str("O-O"),
+            MoveKind::QueensideCastle => san.push_str("O-O-O"),
+            _ if piece == Piece::Pawn => {
+                if kind.is_capture() {
+                    san.push((b'a' + mv.from.0) as char);
+                    san.push('x');
+                }
+                san.push_str(&utils::square_name(mv.to.0, mv.to.1));
+                if kind.is_promotion() {
+                    san.push('=');
+                    san.push(piece_letter(mv.promotion.unwrap_or(Piece::Queen)));
+                }
+            },
+            _ => {
+                san.push(piece_letter(piece));
+
+                // Other pieces of the same kind that may move to the same position
+                let others = (0..PIECE_COUNT)
+                    .filter(|&other| other != id
+                        && team.positions[other] > 0
+                        && team.piece(other) == piece
+                        && self.get_legal_moves(other) & to > 0)
+                    .map(|other| utils::unflatten_bit(team.positions[other]))
+                    .collect::<Vec<_>>();
+
+                if !others.is_empty() {
+                    let file = (b'a' + mv.from.0) as char;
+                    let rank = (b'1' + mv.from.1) as char;
+                    if others.iter().all(|&(x, _)| x != mv.from.0) {
+                        san.push(file);
+                    } else if others.iter().all(|&(_, y)| y != mv.from.1) {
+                        san.push(rank);
+                    } else {
+                        san.push(file);
+                        san.push(rank);
+                    }
+                }
+
+                if kind.is_capture() {
+                    san.push('x');
+                }
+                san.push_str(&utils::square_name(mv.to.0, mv.to.1));
+            },
+        }
+
+        let after = self.after_move(mv);
+        if after.is_checkmate() {
+            san.push('#');
+        } else if after.is_in_check() {
+            san.push('+');
+        }
+
+        san
+    }
+
+    /// Returns the legal move written as `san` in Standard Algebraic Notation, or `None`
+    /// if there is no such move. Check, mate and annotation suffixes are ignored, and
+    /// castling may be written with zeros.
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+
+        let san = normalize(&san.replace('0', "O"));
+        if san.is_empty() {
+            return None;
+        }
+
+        self.legal_move_list()
+            .into_iter()
+            .find(|mv| normalize(&self.san(mv)) == san)
+    }
+}
+
+// Strips suffixes and promotion sign, which are optional
+fn normalize(san: &str) -> String {
+    san.chars()
+        .filter(|c| !matches!(c, '+' | '#' | '!' | '?' | '='))
+        .collect()
+}
+
+fn piece_letter(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn   => 'P',
+        Piece::Knight => 'N',
+        Piece::Bishop => 'B',
+        Piece::Rook   => 'R',
+        Piece::Queen  => 'Q',
+        Piece::King   => 'K',
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::board::*;
+
+    fn san_of(fen: &str, from: (u8, u8), to: (u8, u8), promotion: Option<Piece>) -> String {
+        Board::from_fen(fen).unwrap().san(&Move::new(from, to, promotion))
+    }
+
+    #[test]
+    fn san() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san_of(start, (4, 1), (4, 3), None), "e4");
+        assert_eq!(san_of(start, (6, 0), (5, 2), None), "Nf3");
+
+        // Captures, en passant and castling
+        let fen = "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+        assert_eq!(san_of(fen, (4, 4), (3, 5), None), "exd6");
+        assert_eq!(san_of(fen, (4, 0), (6, 0), None), "O-O");
+        assert_eq!(san_of(fen, (4, 0), (2, 0), None), "O-O-O");
+        assert_eq!(san_of(fen, (0, 0), (0, 7), None), "Rxa8+");
+
+        // Disambiguation by file, rank, and both
+        let fen = "4k3/8/8/8/8/8/4K3/R6R w - - 0 1";
+        assert_eq!(san_of(fen, (0, 0), (3, 0), None), "Rad1");
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san_of(fen, (0, 0), (0, 2), None), "R1a3");
+        let fen = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+        assert_eq!(san_of(fen, (0, 0), (1, 1), None), "Qa1b2");
+
+        // Promotion with mate
+        let fen = "6k1/1P3ppp/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san_of(fen, (1, 6), (1, 7), None), "b8=Q#");
+        assert_eq!(san_of(fen, (1, 6), (1, 7), Some(Piece::Knight)), "b8=N");
+    }
+
+    #[test]
+    fn parse_san() {
+        let board = Board::new();
+        assert_eq!(board.parse_san("e4"), Some(Move::new((4, 1), (4, 3), None)));
+        assert_eq!(board.parse_san("Nf3").map(|mv| mv.from), Some((6, 0)));
+        assert_eq!(board.parse_san("e5"), None);
+        assert_eq!(board.parse_san("Nd2"), None);
+        assert_eq!(board.parse_san(""), None);
+
+        let board = Board::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.parse_san("0-0").map(|mv| mv.to), Some((6, 0)));
+        assert_eq!(board.parse_san("O-O-O").map(|mv| mv.to), Some((2, 0)));
+        assert_eq!(board.parse_san("bxa8=N").and_then(|mv| mv.promotion), Some(Piece::Knight));
+        assert_eq!(board.parse_san("bxa8Q+").and_then(|mv| mv.promotion), Some(Piece::Queen));
+        assert_eq!(board.parse_san("Rxa8+!").map(|mv| mv.from), Some((0, 0)));
+    }
+}
@@ -0,0 +1,126 @@
+
+use super::{ Board, PIECE_COUNT, };
+use crate::piece::Piece;
+use crate::player::Player;
+use crate::utils;
+
+// King can't be captured, so it is only used as a last resort
+fn see_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::King => 20000,
+        _ => piece.value(),
+    }
+}
+
+impl Board {
+
+    /// Static exchange evaluation of capturing the opponent piece at `target`.
+    /// Simulates captures on `target` with the least valuable attacker first,
+    /// alternating players, where each player may stop capturing when it's
+    /// not profitable. Returns the net material outcome in centipawns for the
+    /// current player, or 0 if there is nothing to capture.
+    /// Pins and checks are not taken into account.
+    pub fn see(&self, target: (u8, u8)) -> i32 {
+
+        let pos = utils::flatten_bit(target.0, target.1);
+        let mut board = self.clone();
+        let mut player = self.player;
+
+        let opp_team = match player {
+            Player::White => &self.black,
+            Player::Black => &self.white,
+        };
+
+        let captured = match opp_team.positions.iter().position(|&p| p == pos) {
+            None => return 0,
+            Some(id) => opp_team.piece(id),
+        };
+
+        let mut attacker = board.least_valuable_attacker(pos, player);
+        if attacker.is_none() {
+            return 0;
+        }
+
+        // gain[d] is the material balance after d captures,
+        // assuming the piece that captured last is recaptured
+        let mut gain = vec![see_value(captured)];
+
+        while let Some((id, piece)) = attacker {
+
+            let d = gain.len();
+            gain.push(see_value(piece) - gain[d - 1]);
+
+            // Neither player benefits from continuing
+            if (-gain[d - 1]).max(gain[d]) < 0 {
+                break;
+            }
+
+            match player {
+                Player::White => board.white.positions[id] = 0,
+                Player::Black => board.black.positions[id] = 0,
+            };
+
+            player = match player {
+                Player::White => Player::Black,
+                Player::Black => Player::White,
+            };
+
+            attacker = board.least_valuable_attacker(pos, player);
+        }
+
+        // Last entry assumes a recapture that didn't happen
+        for d in (1..gain.len() - 1).rev() {
+            gain[d - 1] = -(-gain[d - 1]).max(gain[d]);
+        }
+
+        gain[0]
+    }
+
+    fn least_valuable_attacker(&self, pos: u64, by: Player) -> Option<(usize, Piece)> {
+
+        use Player::*;
+
+        let (att_team, def_team, def_player) = match by {
+            White => (&self.white, &self.black, Black, ),
+            Black => (&self.black, &self.white, White, ),
+        };
+
+        let att = Self::attackers(
+            pos,
+            def_team.mask(),
+            att_team.mask(),
+            &att_team.positions,
+            &att_team.promotions,
+            def_player
+        );
+
+        (0..PIECE_COUNT)
+            .filter(|&id| att_team.positions[id] & att > 0)
+            .map(|id| (id, att_team.piece(id)))
+            .min_by_key(|&(_, piece)| see_value(piece))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::board::*;
+
+    #[test]
+    fn see() {
+        // Pawn defended by pawn
+        let board = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(board.see((3, 4)), 100 - 900);
+
+        // Undefended pawn
+        let board = Board::from_fen("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(board.see((3, 4)), 100);
+
+        // Rook behind rook joins the exchange
+        let board = Board::from_fen("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1").unwrap();
+        assert_eq!(board.see((3, 4)), 100);
+
+        // Nothing to capture
+        assert_eq!(board.see((4, 4)), 0);
+    }
+}
@@ -0,0 +1,72 @@
+
+use super::Board;
+use crate::piece::Piece;
+use crate::player::Player;
+use crate::utils;
+
+/// Owned view of a position, for renderers and network layers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Every piece on the board with its owner and position, white first.
+    pub pieces: Vec<(Piece, Player, u8, u8)>,
+    /// Player to move.
+    pub player: Player,
+    /// Castling availability for white, as (kingside, queenside).
+    pub white_castling: (bool, bool),
+    /// Castling availability for black, as (kingside, queenside).
+    pub black_castling: (bool, bool),
+    /// Position a pawn may move to when capturing en passant.
+    pub en_passant: Option<(u8, u8)>,
+    /// Number of half moves since the last capture or pawn move.
+    pub halfmove_clock: u32,
+    /// Number of the full move, starting at 1 and incremented after black moves.
+    pub fullmove_number: u32,
+}
+
+impl Board {
+
+    /// Returns an owned snapshot of the position.
+    pub fn snapshot(&self) -> Snapshot {
+
+        let castling = |rights| if self.castling_disabled { (false, false) } else { rights };
+
+        Snapshot {
+            pieces: self.pieces().collect(),
+            player: self.player,
+            white_castling: castling(self.white.castling_rights()),
+            black_castling: castling(self.black.castling_rights()),
+            en_passant: match self.en_passant_target() {
+                0 => None,
+                t => Some(utils::unflatten_bit(t)),
+            },
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::board::*;
+
+    #[test]
+    fn snapshot() {
+        let snapshot = Board::new().snapshot();
+        assert_eq!(snapshot.pieces.len(), 32);
+        assert_eq!(snapshot.player, Player::White);
+        assert_eq!(snapshot.white_castling, (true, true));
+        assert_eq!(snapshot.black_castling, (true, true));
+        assert_eq!(snapshot.en_passant, None);
+        assert_eq!((snapshot.halfmove_clock, snapshot.fullmove_number), (0, 1));
+
+        let board = Board::from_fen("r3k3/8/8/3pP3/8/8/8/4K2R w Kq d6 3 20").unwrap();
+        let snapshot = board.snapshot();
+        assert_eq!(snapshot.pieces.len(), 6);
+        assert!(snapshot.pieces.contains(&(Piece::Pawn, Player::Black, 3, 4)));
+        assert_eq!(snapshot.white_castling, (true, false));
+        assert_eq!(snapshot.black_castling, (false, true));
+        assert_eq!(snapshot.en_passant, Some((3, 5)));
+        assert_eq!((snapshot.halfmove_clock, snapshot.fullmove_number), (3, 20));
+    }
+}
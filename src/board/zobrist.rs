@@ -0,0 +1,112 @@
+
+use super::Board;
+use crate::player::Player;
+
+// Random keys for each piece of each player on each position,
+// indexed by [player][piece][position]
+const PIECE_KEYS: [[[u64; 64]; 6]; 2] = {
+    let mut keys = [[[0; 64]; 6]; 2];
+    let mut state = 0x9e3779b97f4a7c15;
+    let mut i = 0;
+    while i < 2 * 6 * 64 {
+        let (key, next) = splitmix(state);
+        keys[i / 384][(i / 64) % 6][i % 64] = key;
+        state = next;
+        i += 1;
+    }
+    keys
+};
+
+// Keys for black to move, castling rights in FEN order, and en passant file
+const BLACK_KEY: u64 = splitmix(1).0;
+const CASTLING_KEYS: [u64; 4] = [splitmix(2).0, splitmix(3).0, splitmix(4).0, splitmix(5).0];
+const EN_PASSANT_KEYS: [u64; 8] = {
+    let mut keys = [0; 8];
+    let mut i = 0;
+    while i < 8 {
+        keys[i] = splitmix(16 + i as u64).0;
+        i += 1;
+    }
+    keys
+};
+
+// Returns a pseudo random number and the next state
+const fn splitmix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (z ^ (z >> 31), state)
+}
+
+impl Board {
+
+    /// Returns a Zobrist hash of the position, made up of the same parts as
+    /// [Board::position_key]. Equal positions have equal hashes, and different
+    /// positions have different hashes with high probability.
+    pub fn zobrist_hash(&self) -> u64 {
+
+        let mut hash = 0;
+
+        for (piece, player, x, y) in self.pieces() {
+            hash ^= PIECE_KEYS[player as usize][piece as usize][(x + 8 * y) as usize];
+        }
+
+        if self.player == Player::Black {
+            hash ^= BLACK_KEY;
+        }
+
+        let rights = self.castling_rights();
+        let rights = [
+            rights.white_kingside,
+            rights.white_queenside,
+            rights.black_kingside,
+            rights.black_queenside,
+        ];
+        for (&right, key) in rights.iter().zip(CASTLING_KEYS) {
+            if right {
+                hash ^= key;
+            }
+        }
+
+        let en_passant = self.legal_en_passant_target();
+        if en_passant > 0 {
+            hash ^= EN_PASSANT_KEYS[(en_passant.trailing_zeros() & 7) as usize];
+        }
+
+        hash
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::board::*;
+
+    #[test]
+    fn zobrist_hash() {
+        let board = Board::new();
+        assert_eq!(board.zobrist_hash(), Board::new().zobrist_hash());
+
+        // Same position reached by different move orders
+        let a = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2").unwrap();
+        let b = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 5 9").unwrap();
+        assert_eq!(a.zobrist_hash(), b.zobrist_hash());
+
+        // Player to move, castling and en passant are part of the position
+        let c = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 1 2").unwrap();
+        let d = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b Qkq - 1 2").unwrap();
+        assert_ne!(a.zobrist_hash(), c.zobrist_hash());
+        assert_ne!(a.zobrist_hash(), d.zobrist_hash());
+
+        let e = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let f = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_ne!(e.zobrist_hash(), f.zobrist_hash());
+
+        // En passant that can't be captured is ignored
+        let mut g = Board::new();
+        g.play_move(g.id_from_pos(4, 1).unwrap(), utils::flatten_bit(4, 3));
+        let h = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_eq!(g.zobrist_hash(), h.zobrist_hash());
+    }
+}
@@ -0,0 +1,108 @@
+
+use crate::piece::Piece;
+use crate::utils;
+
+/// A move played by a piece, as recorded in the game history.
+/// Moves are equal if they have the same positions and promotion,
+/// regardless of [Move::kind].
+#[derive(Clone, Copy, Debug)]
+pub struct Move {
+    /// Position of the piece before the move.
+    pub from: (u8, u8),
+    /// Position of the piece after the move.
+    pub to: (u8, u8),
+    /// Piece the pawn was promoted to, if the move was a promotion.
+    pub promotion: Option<Piece>,
+    /// Kind of move.
+    pub kind: MoveKind,
+}
+
+impl Move {
+
+    /// Creates a move. Kind is set to [MoveKind::Quiet],
+    /// and is determined by the game when the move is played.
+    pub fn new(from: (u8, u8), to: (u8, u8), promotion: Option<Piece>) -> Move {
+        Move { from, to, promotion, kind: MoveKind::Quiet, }
+    }
+
+    /// Returns the move in UCI long algebraic notation, e.g. `"e2e4"` or `"e7e8q"`.
+    pub fn to_uci(&self) -> String {
+
+        let mut uci = utils::square_name(self.from.0, self.from.1)
+            + &utils::square_name(self.to.0, self.to.1);
+
+        if let Some(piece) = self.promotion {
+            uci.push(match piece {
+                Piece::Knight => 'n',
+                Piece::Bishop => 'b',
+                Piece::Rook   => 'r',
+                _             => 'q',
+            });
+        }
+
+        uci
+    }
+
+    /// Parses a move in UCI long algebraic notation, e.g. `"g1f3"` or `"a2a1n"`.
+    /// Returns `None` if the string is not a move on the board. Legality is not checked.
+    pub fn from_uci(uci: &str) -> Option<Move> {
+
+        let from = utils::parse_square(uci.get(0..2)?)?;
+        let to = utils::parse_square(uci.get(2..4)?)?;
+
+        let promotion = match uci.get(4..)? {
+            ""  => None,
+            "q" => Some(Piece::Queen),
+            "r" => Some(Piece::Rook),
+            "b" => Some(Piece::Bishop),
+            "n" => Some(Piece::Knight),
+            _   => return None,
+        };
+
+        Some(Move::new(from, to, promotion))
+    }
+}
+
+// The kind follows from the position, and is not known for moves created
+// with Move::new until they are played
+impl PartialEq for Move {
+    fn eq(&self, other: &Self) -> bool {
+        self.from == other.from && self.to == other.to && self.promotion == other.promotion
+    }
+}
+
+impl Eq for Move {}
+
+/// Classification of moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveKind {
+    /// Move to an empty position.
+    Quiet,
+    /// Pawn moving two positions forward.
+    DoublePush,
+    /// Capture of an opponent piece.
+    Capture,
+    /// Pawn capturing en passant.
+    EnPassant,
+    /// King castling towards the h-file.
+    KingsideCastle,
+    /// King castling towards the a-file.
+    QueensideCastle,
+    /// Pawn reaching the last rank.
+    Promotion,
+    /// Pawn capturing on the last rank.
+    PromotionCapture,
+}
+
+impl MoveKind {
+
+    /// Returns true if the move captures a piece, including en passant.
+    pub fn is_capture(self) -> bool {
+        matches!(self, MoveKind::Capture | MoveKind::EnPassant | MoveKind::PromotionCapture)
+    }
+
+    /// Returns true if the move promotes a pawn.
+    pub fn is_promotion(self) -> bool {
+        matches!(self, MoveKind::Promotion | MoveKind::PromotionCapture)
+    }
+}
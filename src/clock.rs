@@ -0,0 +1,40 @@
+
+use crate::player::Player;
+
+/// Time control for a game. All times are in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clock {
+    /// Time left for white.
+    pub white_ms: u64,
+    /// Time left for black.
+    pub black_ms: u64,
+    /// Time added to a player's clock after each of their moves.
+    pub increment_ms: u64,
+}
+
+impl Clock {
+
+    /// Creates a clock with the same time for both players.
+    pub fn new(time_ms: u64, increment_ms: u64) -> Clock {
+        Clock {
+            white_ms: time_ms,
+            black_ms: time_ms,
+            increment_ms,
+        }
+    }
+
+    /// Returns time left for `player`.
+    pub fn remaining(&self, player: Player) -> u64 {
+        match player {
+            Player::White => self.white_ms,
+            Player::Black => self.black_ms,
+        }
+    }
+
+    pub(crate) fn remaining_mut(&mut self, player: Player) -> &mut u64 {
+        match player {
+            Player::White => &mut self.white_ms,
+            Player::Black => &mut self.black_ms,
+        }
+    }
+}
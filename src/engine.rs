@@ -0,0 +1,682 @@
+
+//! Evaluation and search.
+
+use std::time::{ Duration, Instant, };
+
+use crate::{
+    board::Board,
+    utils,
+    chess_move::Move,
+    moves::MOVES,
+    piece::Piece,
+    player::Player,
+};
+
+// Score of being checkmated, adjusted by distance so that faster mates are preferred
+const MATE: i32 = 1_000_000;
+
+// Area of the bare king at most which kqk_best_move searches deeper for mate
+const KQK_AREA: u32 = 4;
+
+// Iterative deepening stops at this depth even if time remains
+const MAX_DEPTH: u32 = 64;
+
+// Piece-square tables from white's perspective, indexed by [Piece as usize].
+// Rows are ranks from 8 to 1, so that tables read like a board.
+const PST: [[i32; 64]; 6] = [
+    // Pawn
+    [
+         0,   0,   0,   0,   0,   0,   0,   0,
+        50,  50,  50,  50,  50,  50,  50,  50,
+        10,  10,  20,  30,  30,  20,  10,  10,
+         5,   5,  10,  25,  25,  10,   5,   5,
+         0,   0,   0,  20,  20,   0,   0,   0,
+         5,  -5, -10,   0,   0, -10,  -5,   5,
+         5,  10,  10, -20, -20,  10,  10,   5,
+         0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Knight
+    [
+       -50, -40, -30, -30, -30, -30, -40, -50,
+       -40, -20,   0,   0,   0,   0, -20, -40,
+       -30,   0,  10,  15,  15,  10,   0, -30,
+       -30,   5,  15,  20,  20,  15,   5, -30,
+       -30,   0,  15,  20,  20,  15,   0, -30,
+       -30,   5,  10,  15,  15,  10,   5, -30,
+       -40, -20,   0,   5,   5,   0, -20, -40,
+       -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    // Bishop
+    [
+       -20, -10, -10, -10, -10, -10, -10, -20,
+       -10,   0,   0,   0,   0,   0,   0, -10,
+       -10,   0,   5,  10,  10,   5,   0, -10,
+       -10,   5,   5,  10,  10,   5,   5, -10,
+       -10,   0,  10,  10,  10,  10,   0, -10,
+       -10,  10,  10,  10,  10,  10,  10, -10,
+       -10,   5,   0,   0,   0,   0,   5, -10,
+       -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // Rook
+    [
+         0,   0,   0,   0,   0,   0,   0,   0,
+         5,  10,  10,  10,  10,  10,  10,   5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+         0,   0,   0,   5,   5,   0,   0,   0,
+    ],
+    // Queen
+    [
+       -20, -10, -10,  -5,  -5, -10, -10, -20,
+       -10,   0,   0,   0,   0,   0,   0, -10,
+       -10,   0,   5,   5,   5,   5,   0, -10,
+        -5,   0,   5,   5,   5,   5,   0,  -5,
+         0,   0,   5,   5,   5,   5,   0,  -5,
+       -10,   5,   5,   5,   5,   5,   0, -10,
+       -10,   0,   5,   0,   0,   0,   0, -10,
+       -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    // King
+    [
+       -30, -40, -40, -50, -50, -40, -40, -30,
+       -30, -40, -40, -50, -50, -40, -40, -30,
+       -30, -40, -40, -50, -50, -40, -40, -30,
+       -30, -40, -40, -50, -50, -40, -40, -30,
+       -20, -30, -30, -40, -40, -30, -30, -20,
+       -10, -20, -20, -20, -20, -20, -20, -10,
+        20,  20,   0,   0,   0,   0,  20,  20,
+        20,  30,  10,   0,   0,  10,  30,  20,
+    ],
+];
+
+// Material and piece-square value of piece at (x, y)
+fn piece_score(piece: Piece, player: Player, x: u8, y: u8) -> i32 {
+    let row = match player {
+        Player::White => 7 - y,
+        Player::Black => y,
+    };
+    piece.value() + PST[piece as usize][(x + 8 * row) as usize]
+}
+
+impl Board {
+
+    /// Returns game phase for blending middlegame and endgame evaluation, from 24 with
+    /// all pieces on the board down to 0 when only kings and pawns remain. Each knight
+    /// and bishop counts 1, rook 2 and queen 4. Capped at 24 if there are promoted pieces.
+    pub fn game_phase(&self) -> u32 {
+
+        let phase = [Player::White, Player::Black].iter()
+            .map(|&player| {
+                let counts = self.all_counts(player);
+                counts[Piece::Knight as usize]
+                    + counts[Piece::Bishop as usize]
+                    + counts[Piece::Rook as usize] * 2
+                    + counts[Piece::Queen as usize] * 4
+            })
+            .sum::<u32>();
+
+        phase.min(24)
+    }
+
+    /// Returns material of white minus material of black, in centipawns.
+    pub fn material_balance(&self) -> i32 {
+        self.white_iter().map(|(piece, _, _)| piece.value()).sum::<i32>()
+            - self.black_iter().map(|(piece, _, _)| piece.value()).sum::<i32>()
+    }
+
+    /// Returns the number of pieces white has minus the number black has of each
+    /// kind, indexed by [Piece] as `usize`. Promoted pawns count as their new piece.
+    pub fn material_imbalance(&self) -> [i32; 6] {
+        let white = self.all_counts(Player::White);
+        let black = self.all_counts(Player::Black);
+        std::array::from_fn(|i| white[i] as i32 - black[i] as i32)
+    }
+
+    /// Returns true if `player` has bishops on both light and dark squares.
+    /// Promoted pawns count as their new piece.
+    pub fn has_bishop_pair(&self, player: Player) -> bool {
+
+        let pieces = match player {
+            Player::White => self.white_iter(),
+            Player::Black => self.black_iter(),
+        };
+
+        let mut colors = [false; 2];
+        for (_, x, y) in pieces.filter(|&(piece, _, _)| piece == Piece::Bishop) {
+            colors[((x + y) % 2) as usize] = true;
+        }
+
+        colors[0] && colors[1]
+    }
+
+    /// Returns material and piece-square score in centipawns,
+    /// from the perspective of the current player.
+    pub fn positional_score(&self) -> i32 {
+
+        let white = self.white_iter()
+            .map(|(piece, x, y)| piece_score(piece, Player::White, x, y))
+            .sum::<i32>();
+        let black = self.black_iter()
+            .map(|(piece, x, y)| piece_score(piece, Player::Black, x, y))
+            .sum::<i32>();
+
+        match self.player {
+            Player::White => white - black,
+            Player::Black => black - white,
+        }
+    }
+}
+
+/// Weights of the terms of [Board::evaluate].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    /// Material value of each piece in centipawns, indexed by `Piece as usize`.
+    pub material: [i32; 6],
+    /// Multiplier of the piece-square table bonuses.
+    pub piece_square_weight: i32,
+    /// Centipawns per legal move more than the opponent.
+    pub mobility_weight: i32,
+    /// Centipawns per passed pawn, and penalty per doubled or isolated pawn.
+    pub pawn_structure_weight: i32,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        use Piece::*;
+        Self {
+            material: [Pawn, Knight, Bishop, Rook, Queen, King].map(Piece::value),
+            piece_square_weight: 1,
+            mobility_weight: 2,
+            pawn_structure_weight: 10,
+        }
+    }
+}
+
+impl Board {
+
+    /// Returns score of position in centipawns from the perspective of the
+    /// current player, with terms weighted by `params`.
+    pub fn evaluate(&self, params: &EvalParams) -> i32 {
+        self.evaluate_with_mobility(params, self.legal_move_count())
+    }
+
+    // Same as evaluate, with the number of legal moves of the current player
+    // given, so that the search doesn't generate them again
+    fn evaluate_with_mobility(&self, params: &EvalParams, mobility: u32) -> i32 {
+
+        let score = |player: Player, mobility: u32| {
+
+            let pieces = match player {
+                Player::White => self.white_iter(),
+                Player::Black => self.black_iter(),
+            };
+            let row = |y: u8| match player {
+                Player::White => 7 - y,
+                Player::Black => y,
+            };
+
+            let material = pieces
+                .map(|(piece, x, y)| params.material[piece as usize]
+                    + params.piece_square_weight * PST[piece as usize][(x + 8 * row(y)) as usize])
+                .sum::<i32>();
+
+            let pawns = self.pawn_structure(player);
+            let pawns = pawns.passed as i32 - pawns.doubled as i32 - pawns.isolated as i32;
+
+            material
+                + params.mobility_weight * mobility as i32
+                + params.pawn_structure_weight * pawns
+        };
+
+        let opponent = self.player.opponent();
+        let opp_mobility = match params.mobility_weight {
+            0 => 0,
+            _ => self.mobility(opponent),
+        };
+
+        score(self.player, mobility) - score(opponent, opp_mobility)
+    }
+}
+
+/// Returns the best move for the current player found by searching deeper
+/// and deeper until `max_ms` milliseconds have passed. The result of the
+/// deepest completed search is used. Returns `None` if there are no legal moves.
+pub fn best_move_timed(board: &Board, max_ms: u64) -> Option<Move> {
+
+    let deadline = Instant::now() + Duration::from_millis(max_ms);
+
+    // Any legal move, in case not even depth 1 completes
+    let mut best = *board.legal_move_list().first()?;
+
+    for depth in 1..=MAX_DEPTH {
+        match search_root(board, depth, deadline) {
+            Some(mv) => best = mv,
+            None => break,
+        }
+    }
+
+    Some(best)
+}
+
+// Best move at depth, or None if the deadline passed
+fn search_root(board: &Board, depth: u32, deadline: Instant) -> Option<Move> {
+
+    let mut best = None;
+    let mut alpha = -MATE - 1;
+
+    for mv in ordered_moves(board) {
+        let score = -negamax(&board.after_move(&mv), depth - 1, 1, -MATE - 1, -alpha, deadline)?;
+        if score > alpha {
+            alpha = score;
+            best = Some(mv);
+        }
+    }
+
+    best
+}
+
+// Score of position from the perspective of the current player,
+// or None if the deadline passed
+fn negamax(board: &Board, depth: u32, ply: i32, mut alpha: i32, beta: i32, deadline: Instant) -> Option<i32> {
+
+    if Instant::now() >= deadline {
+        return None;
+    }
+
+    let moves = ordered_moves(board);
+
+    if moves.is_empty() {
+        return Some(if board.is_in_check() { -MATE + ply } else { 0 });
+    }
+
+    if depth == 0 {
+        // Promotions are listed once per piece, but count as one move
+        let mobility = moves.iter()
+            .filter(|mv| matches!(mv.promotion, None | Some(Piece::Queen)))
+            .count();
+        return Some(board.evaluate_with_mobility(&EvalParams::default(), mobility as u32));
+    }
+
+    for mv in moves {
+        let score = -negamax(&board.after_move(&mv), depth - 1, ply + 1, -beta, -alpha, deadline)?;
+        if score >= beta {
+            return Some(beta);
+        }
+        alpha = alpha.max(score);
+    }
+
+    Some(alpha)
+}
+
+/// Returns a move for the current player with king and queen against a bare king,
+/// or `None` for any other material. Rather than using a tablebase, a short mate is
+/// played if one is found, and otherwise the move is chosen to shrink the area the
+/// opponent king is confined to, drive it towards a corner and bring the kings
+/// closer, looking one reply ahead. Moves that stalemate or leave the queen to be
+/// captured are never chosen.
+pub fn kqk_best_move(board: &Board) -> Option<Move> {
+
+    use Piece::*;
+
+    let mut attacker = [0; 6];
+    attacker[Queen as usize] = 1;
+    attacker[King as usize] = 1;
+    let mut defender = [0; 6];
+    defender[King as usize] = 1;
+
+    if board.all_counts(board.player) != attacker
+        || board.all_counts(board.player.opponent()) != defender
+    {
+        return None;
+    }
+
+    // Look further for mate once the king is confined
+    let mut opp = board.clone();
+    opp.set_to_move(board.player.opponent());
+    let area = king_area(&opp, king_square(board, opp.player)).count_ones();
+    let depth = if area <= KQK_AREA { 3 } else { 2 };
+
+    if let Some(mv) = mate_in(board, depth) {
+        return Some(mv);
+    }
+
+    board.legal_move_list()
+        .into_iter()
+        .filter_map(|mv| {
+
+            let after = board.after_move(&mv);
+            let score = kqk_score(&after)?;
+
+            // Best follow up against the most stubborn reply
+            let worst = after.legal_move_list()
+                .iter()
+                .map(|reply| {
+                    let after = after.after_move(reply);
+                    after.legal_move_list()
+                        .iter()
+                        .filter_map(|mv| kqk_score(&after.after_move(mv)))
+                        .min()
+                        .unwrap_or(u32::MAX)
+                })
+                .max()
+                .unwrap_or(u32::MAX);
+
+            Some(((worst, score), mv))
+        })
+        .min_by_key(|&(key, _)| key)
+        .map(|(_, mv)| mv)
+}
+
+/// Returns a move that forces checkmate within `n` moves of the current player
+/// against any defence, or `None` if there is no such move. The fastest mate is
+/// preferred. Searches all moves, so `n` should be small.
+pub fn mate_in(board: &Board, n: u32) -> Option<Move> {
+    (1..=n).find_map(|n| forced_mate(board, n))
+}
+
+// Move that mates in at most n moves against any defence
+fn forced_mate(board: &Board, n: u32) -> Option<Move> {
+
+    if n == 1 {
+        return board.checking_moves()
+            .into_iter()
+            .find(|mv| board.after_move(mv).is_checkmate());
+    }
+
+    board.legal_move_list().into_iter().find(|mv| {
+        let after = board.after_move(mv);
+        if after.is_checkmate() {
+            return true;
+        }
+        let replies = after.legal_move_list();
+        !replies.is_empty() && replies.iter()
+            .all(|reply| forced_mate(&after.after_move(reply), n - 1).is_some())
+    })
+}
+
+// Score of a king and queen against king position with the bare king to move,
+// lower being better. Zero if checkmate, None if stalemate or the queen may
+// be captured.
+fn kqk_score(board: &Board) -> Option<u32> {
+
+    let moves = board.legal_move_list();
+
+    if moves.is_empty() {
+        return board.is_in_check().then_some(0);
+    }
+
+    // Any capture by the bare king is of the queen
+    if moves.iter().any(|mv| mv.kind.is_capture()) {
+        return None;
+    }
+
+    let king = king_square(board, board.player);
+    let distance = utils::manhattan_distance(king, king_square(board, board.player.opponent()));
+    let area = king_area(board, king).count_ones();
+    let (x, y) = utils::unflatten(king);
+    let corner = x.min(7 - x) + y.min(7 - y);
+
+    Some(1 + 8 * area + 4 * corner as u32 + distance as u32)
+}
+
+fn king_square(board: &Board, player: Player) -> usize {
+    board.pieces()
+        .find(|&(piece, owner, _, _)| piece == Piece::King && owner == player)
+        .map(|(_, _, x, y)| utils::flatten(x, y))
+        .unwrap()
+}
+
+// Positions the king of the current player, at sq, could reach in any
+// number of moves if the opponent stood still
+fn king_area(board: &Board, sq: usize) -> u64 {
+
+    let free = !board.attack_map(board.player.opponent());
+
+    let mut area = 1 << sq;
+    loop {
+        let grown = utils::BitIterator::new(area)
+            .fold(area, |m, p| m | MOVES.king_moves[p.trailing_zeros() as usize])
+            & (free | area);
+        if grown == area {
+            return area;
+        }
+        area = grown;
+    }
+}
+
+/// Plays uniformly random legal moves from the initial position until the game
+/// is over or `max_plies` half moves have been played, and returns the moves.
+/// The same `seed` always gives the same game. Useful for fuzzing move generation.
+pub fn random_game(seed: u64, max_plies: u32) -> Vec<Move> {
+
+    // Mix the seed with splitmix64, so that nearby seeds give unrelated games
+    let mut state = seed.wrapping_add(0x9e3779b97f4a7c15);
+    state = (state ^ (state >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    state = (state ^ (state >> 27)).wrapping_mul(0x94d049bb133111eb);
+    state ^= state >> 31;
+
+    // Xorshift state must not be zero
+    if state == 0 {
+        state = 0x9e3779b97f4a7c15;
+    }
+
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut board = Board::new();
+    let mut moves = Vec::new();
+
+    while moves.len() < max_plies as usize && !board.is_insufficient_material() {
+
+        let list = board.legal_move_list();
+        if list.is_empty() {
+            break;
+        }
+
+        let mv = list[(next() % list.len() as u64) as usize];
+        board = board.after_move(&mv);
+        moves.push(mv);
+    }
+
+    moves
+}
+
+// Legal moves with captures first, which speeds up alpha-beta pruning
+fn ordered_moves(board: &Board) -> Vec<Move> {
+    let mut moves = board.legal_move_list();
+    moves.sort_by_key(|mv| !mv.kind.is_capture());
+    moves
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::engine::*;
+    use crate::game::Game;
+
+    #[test]
+    fn positional_score() {
+        let center = Board::from_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let rim = Board::from_fen("4k3/8/8/8/N7/8/8/4K3 w - - 0 1").unwrap();
+        assert!(center.positional_score() > rim.positional_score());
+
+        // Symmetric from black's perspective
+        let black = Board::from_fen("4k3/8/8/3n4/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(black.positional_score(), center.positional_score());
+
+        assert_eq!(Board::new().positional_score(), 0);
+    }
+
+    #[test]
+    fn evaluate() {
+        let params = EvalParams::default();
+        assert_eq!(Board::new().evaluate(&params), 0);
+
+        // Only material and piece-square tables
+        let board = Board::from_fen("r1bqk2r/pppp1ppp/2n2n2/4p3/1bB1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 4 5").unwrap();
+        let positional = EvalParams { mobility_weight: 0, pawn_structure_weight: 0, ..params };
+        assert_eq!(board.evaluate(&positional), board.positional_score());
+
+        // Rook behind its own pawn has fewer moves
+        let open = Board::from_fen("4k3/8/8/8/8/8/P7/1R2K3 w - - 0 1").unwrap();
+        let closed = Board::from_fen("4k3/8/8/8/8/8/P7/R3K3 w - - 0 1").unwrap();
+        assert!(open.evaluate(&params) - open.positional_score()
+            > closed.evaluate(&params) - closed.positional_score());
+
+        // Zero weights
+        let zero = EvalParams {
+            material: [0; 6],
+            piece_square_weight: 0,
+            mobility_weight: 0,
+            pawn_structure_weight: 0,
+        };
+        assert_eq!(open.evaluate(&zero), 0);
+
+        // Up a knight
+        let mut board = Board::from_fen("4k3/pppp4/8/8/8/5N2/PPPP4/4K3 w - - 0 1").unwrap();
+        assert!(board.evaluate(&params) > 0);
+        board.set_to_move(Player::Black);
+        assert!(board.evaluate(&params) < 0);
+
+        // The search counts mobility from its move list, promotions once
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let score = super::negamax(&board, 0, 0, -MATE - 1, MATE + 1, deadline).unwrap();
+        assert_eq!(score, board.evaluate(&params));
+    }
+
+    #[test]
+    fn mate_in() {
+        // Back rank mate
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mv = super::mate_in(&board, 1).unwrap();
+        assert_eq!((mv.from, mv.to), ((0, 0), (0, 7)));
+        assert_eq!(super::mate_in(&board, 3), Some(mv));
+        assert_eq!(super::mate_in(&board, 0), None);
+
+        // Mate in two with a rook and a king against a cornered king
+        let board = Board::from_fen("7k/8/5K2/8/8/8/8/1R6 w - - 0 1").unwrap();
+        assert_eq!(super::mate_in(&board, 1), None);
+        let mv = super::mate_in(&board, 2).unwrap();
+        let after = board.after_move(&mv);
+        assert!(after.legal_move_list().iter()
+            .all(|reply| super::mate_in(&after.after_move(reply), 1).is_some()));
+
+        assert_eq!(super::mate_in(&Board::new(), 2), None);
+    }
+
+    #[test]
+    fn kqk_best_move() {
+        assert_eq!(super::kqk_best_move(&Board::new()), None);
+        let krk = Board::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(super::kqk_best_move(&krk), None);
+
+        let mut board = Board::from_fen("8/8/8/4k3/8/8/8/3QK3 w - - 0 1").unwrap();
+        let before = board.mobility(Player::Black);
+
+        let mv = super::kqk_best_move(&board).unwrap();
+        board = board.after_move(&mv);
+        assert!(board.mobility(Player::Black) < before);
+        assert!(!board.is_stalemate());
+
+        // Plays on until mate against random replies, never stalemating
+        let mut seed = 1u64;
+        for _ in 0..60 {
+            if board.is_checkmate() {
+                break;
+            }
+            let replies = board.legal_move_list();
+            assert!(!replies.is_empty());
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            board = board.after_move(&replies[(seed >> 33) as usize % replies.len()]);
+            let mv = super::kqk_best_move(&board).unwrap();
+            board = board.after_move(&mv);
+        }
+        assert!(board.is_checkmate());
+
+        // Mate in one is taken
+        let board = Board::from_fen("k7/8/1K6/8/8/8/7Q/8 w - - 0 1").unwrap();
+        assert!(board.after_move(&super::kqk_best_move(&board).unwrap()).is_checkmate());
+    }
+
+    #[test]
+    fn game_phase() {
+        assert_eq!(Board::new().game_phase(), 24);
+        assert_eq!(Board::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap().game_phase(), 0);
+        assert_eq!(Board::from_fen("3qk3/8/8/8/8/8/8/R3K1N1 w - - 0 1").unwrap().game_phase(), 7);
+        assert_eq!(Board::from_fen("QQQQQQQ1/8/8/8/8/8/8/4K2k b - - 0 1").unwrap().game_phase(), 24);
+    }
+
+    #[test]
+    fn material_imbalance() {
+        assert_eq!(Board::new().material_imbalance(), [0; 6]);
+
+        // White is up the exchange and two pawns
+        let board = Board::from_fen("rn2k3/pp6/8/8/8/8/PPPP4/R3K2R w - - 0 1").unwrap();
+        let mut expected = [0; 6];
+        expected[Piece::Pawn as usize] = 2;
+        expected[Piece::Rook as usize] = 1;
+        expected[Piece::Knight as usize] = -1;
+        assert_eq!(board.material_imbalance(), expected);
+    }
+
+    #[test]
+    fn has_bishop_pair() {
+        let board = Board::new();
+        assert!(board.has_bishop_pair(Player::White));
+        assert!(board.has_bishop_pair(Player::Black));
+
+        // Black has traded a bishop
+        let board = Board::from_fen("rn1qkbnr/ppp1pppp/8/3p4/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert!(board.has_bishop_pair(Player::White));
+        assert!(!board.has_bishop_pair(Player::Black));
+
+        // Both white bishops on dark squares
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1").unwrap();
+        assert!(!board.has_bishop_pair(Player::White));
+
+        // Promoted bishop on a light square
+        let board = Board::from_fen("7k/P7/8/8/8/8/8/2B1K3 w - - 0 1").unwrap()
+            .after_move(&Move::new((0, 6), (0, 7), Some(Piece::Bishop)));
+        assert!(board.has_bishop_pair(Player::White));
+    }
+
+    #[test]
+    fn best_move_timed() {
+        let board = Board::from_fen("k7/8/8/3q4/4P3/8/8/7K w - - 0 1").unwrap();
+        let mv = super::best_move_timed(&board, 200).unwrap();
+        assert_eq!((mv.from, mv.to), ((4, 3), (3, 4)));
+
+        // Mate in one
+        let board = Board::from_fen("7k/6pp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mv = super::best_move_timed(&board, 200).unwrap();
+        assert_eq!((mv.from, mv.to), ((0, 0), (0, 7)));
+
+        let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(super::best_move_timed(&board, 10), None);
+
+        // Some legal move is returned even without time to search
+        assert!(super::best_move_timed(&Board::new(), 0).is_some());
+    }
+
+    #[test]
+    fn random_game() {
+        assert_eq!(super::random_game(7, 40), super::random_game(7, 40));
+        assert_ne!(super::random_game(7, 40), super::random_game(8, 40));
+        assert_ne!(super::random_game(2, 50), super::random_game(3, 50));
+
+        for seed in 0..200 {
+            let moves = super::random_game(seed, 300);
+            let game = Game::replay(&moves).unwrap();
+            // Either the game is over, or it was cut short
+            assert_eq!(game.result().is_some(), moves.len() < 300, "seed {}", seed);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 
 
 /// Error variants for [crate::Game] methods.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Error {
     /// The method was called in the incorrect state.
     InvalidState,
@@ -9,4 +9,22 @@ pub enum Error {
     InvalidPosition,
     /// The piece provided is not a valid promotion.
     InvalidPromotion,
+    /// The move is not legal. Contains the ply (index into the move history)
+    /// at which the move was attempted.
+    InvalidMove(usize),
+    /// The string is not a valid Forsyth-Edwards Notation position.
+    InvalidFen,
+    /// The position is not legal.
+    IllegalPosition,
+    /// The game has no position at the requested ply.
+    InvalidPly,
+    /// There is no move to take back.
+    NothingToUndo,
+    /// There is no taken back move to play again.
+    NothingToRedo,
+    /// Neither the fifty-move rule nor threefold repetition allows claiming a draw.
+    NoDrawToClaim,
+    /// A move in Standard Algebraic Notation is malformed or not legal. Contains
+    /// the index of its token among the whitespace separated tokens of the line.
+    InvalidSan(usize),
 }
@@ -1,9 +1,15 @@
 
+use std::collections::HashMap;
+use std::fmt;
+
 use crate::{
     error::Error,
+    chess_move::{ Move, MoveKind, },
     piece::Piece,
     player::Player,
-    board::Board,
+    board::{ Board, Outcome, },
+    clock::Clock,
+    game_result::GameResult,
     utils,
 };
 
@@ -16,6 +22,46 @@ pub struct Game {
     selected_moves: (u64, Vec<(u8, u8)>),
     black_positions: Vec<(Piece, u8, u8)>,
     white_positions: Vec<(Piece, u8, u8)>,
+    history: Vec<Move>,
+    // moves stepped back over, next move last
+    redo: Vec<Move>,
+    // position before the first move
+    start: Board,
+    // position before the first move, if not the standard initial position
+    started_from_fen: Option<String>,
+    // zobrist hash of each position since the start, current position last
+    hashes: Vec<u64>,
+    options: GameOptions,
+    clock: Option<Clock>,
+    // clock before the first move
+    start_clock: Option<Clock>,
+    draw_offer: Option<Player>,
+    draw_reason: Option<DrawReason>,
+    last_move_gave_check: bool,
+}
+
+/// Options for configuring game rules.
+#[derive(Clone, Debug)]
+pub struct GameOptions {
+    /// Promote pawns to [Piece::Queen] directly, without entering
+    /// [State::SelectPromotion], if the queen is an allowed promotion.
+    pub auto_queen: bool,
+    /// Allow castling. If disabled, kings never have castling moves.
+    pub castling_enabled: bool,
+    /// Pieces pawns may be promoted to. Pieces other than [Piece::Rook],
+    /// [Piece::Knight], [Piece::Bishop] and [Piece::Queen] are ignored.
+    /// If no piece is left, all four are allowed.
+    pub allowed_promotions: Vec<Piece>,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        Self {
+            auto_queen: false,
+            castling_enabled: true,
+            allowed_promotions: vec![Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight],
+        }
+    }
 }
 
 /// Represents the current state of the game.
@@ -27,14 +73,82 @@ pub enum State {
     SelectMove,
     /// Current player is in checkmate.
     CheckMate,
+    /// Current player has no legal moves, but is not in check.
+    Stalemate,
+    /// Current player has run out of time.
+    TimeOut,
+    /// The game is drawn, see [Game::draw_reason] for why.
+    Draw,
     /// Current player needs to select a promotion
     SelectPromotion,
 }
 
+impl State {
+
+    /// Returns a short lowercase description of the state, e.g. `"select piece"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            State::SelectPiece     => "select piece",
+            State::SelectMove      => "select move",
+            State::CheckMate       => "checkmate",
+            State::Stalemate       => "stalemate",
+            State::TimeOut         => "time out",
+            State::Draw            => "draw",
+            State::SelectPromotion => "select promotion",
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+/// Outcome of [Game::try_select_piece].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectOutcome {
+    /// The piece was selected and has legal moves.
+    Selected,
+    /// The position is empty, nothing was selected.
+    EmptySquare,
+    /// The position is occupied by the opponent, nothing was selected.
+    OpponentPiece,
+    /// The piece was selected, but has no legal moves.
+    NoLegalMoves,
+}
+
+/// Reason a game was drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawReason {
+    /// The current player has no legal moves, but is not in check.
+    Stalemate,
+    /// Draw claimed after fifty moves without captures or pawn moves.
+    FiftyMove,
+    /// Draw claimed after the same position occurred three times.
+    Threefold,
+    /// Neither player has sufficient material to checkmate.
+    InsufficientMaterial,
+    /// The players agreed to a draw.
+    Agreement,
+}
+
 impl Game {
 
     /// Creates a new game with pieces in inital positions.
     pub fn new() -> Game {
+        Game::with_options(GameOptions::default())
+    }
+
+    /// Creates a new game with pieces in inital positions, using the provided options.
+    pub fn with_options(options: GameOptions) -> Game {
+        let castling_enabled = options.castling_enabled;
         let mut game = Game {
             state: State::SelectPiece,
             board: Board::new(),
@@ -43,15 +157,226 @@ impl Game {
             selected_moves: (0, Vec::new()),
             black_positions: Vec::new(),
             white_positions: Vec::new(),
+            history: Vec::new(),
+            redo: Vec::new(),
+            start: Board::new(),
+            started_from_fen: None,
+            hashes: Vec::new(),
+            options,
+            clock: None,
+            start_clock: None,
+            draw_offer: None,
+            draw_reason: None,
+            last_move_gave_check: false,
         };
 
+        // Pawns on the last rank must be able to promote to something
+        if game.promotion_choices().is_empty() {
+            game.options.allowed_promotions = GameOptions::default().allowed_promotions;
+        }
+
+        game.board.set_castling_enabled(castling_enabled);
+        game.start = game.board.clone();
+        game.hashes.push(game.board.zobrist_hash());
         game.update_positions();
         game
     }
 
-    /// Resets the game to its initial state
+    /// Creates a new game with pieces in inital positions, using the provided clock.
+    /// The clock is charged with [Game::tick].
+    pub fn with_clock(clock: Clock) -> Game {
+        let mut game = Game::new();
+        game.clock = Some(clock);
+        game.start_clock = Some(clock);
+        game
+    }
+
+    /// Creates a game from a position in Forsyth-Edwards Notation.
+    /// Returns [Error::InvalidFen] if the string is malformed, and
+    /// [Error::IllegalPosition] if the position is not legal.
+    pub fn from_fen(fen: &str) -> Result<Game, Error> {
+        let mut game = Game::new();
+        game.set_board(Board::from_fen(fen)?);
+        game.start = game.board.clone();
+        game.started_from_fen = game.custom_start_fen();
+        Ok(game)
+    }
+
+    /// Creates a game from a line in Extended Position Description: the first four
+    /// fields of a Forsyth-Edwards Notation position, followed by operations
+    /// terminated by `;`, e.g. `bm Nf3; id "test 1";`.
+    /// Returns the game and the operands of each operation by opcode. Operands are
+    /// stored as written, except that quotes around strings are removed.
+    /// Returns [Error::InvalidFen] if the line is malformed, and
+    /// [Error::IllegalPosition] if the position is not legal.
+    pub fn from_epd(epd: &str) -> Result<(Game, HashMap<String, String>), Error> {
+
+        let epd = epd.trim_start();
+
+        // Position is the first four fields
+        let mut end = 0;
+        for _ in 0..4 {
+            let rest = &epd[end..];
+            let start = end + rest.len() - rest.trim_start().len();
+            end = match epd[start..].find(char::is_whitespace) {
+                Some(i) => start + i,
+                None => epd.len(),
+            };
+        }
+
+        let game = Game::from_fen(&epd[..end])?;
+        let mut operations = HashMap::new();
+
+        let mut operation = String::new();
+        let mut quoted = false;
+
+        for c in epd[end..].chars() {
+            match c {
+                '"' => quoted = !quoted,
+                ';' if !quoted => {
+                    let (opcode, operand) = match operation.trim().split_once(char::is_whitespace) {
+                        Some((opcode, operand)) => (opcode, operand.trim()),
+                        None => (operation.trim(), ""),
+                    };
+                    if opcode.is_empty() {
+                        return Err(Error::InvalidFen);
+                    }
+                    operations.insert(opcode.to_string(), operand.to_string());
+                    operation.clear();
+                },
+                c => operation.push(c),
+            }
+        }
+
+        // Operations must be terminated
+        if quoted || !operation.trim().is_empty() {
+            return Err(Error::InvalidFen);
+        }
+
+        Ok((game, operations))
+    }
+
+    /// Creates a new game and plays the provided moves in order.
+    /// Promotions are applied from [Move::promotion].
+    /// Returns [Error::InvalidMove] with the index of the first move that is not legal,
+    /// or that is missing a required promotion.
+    pub fn replay(moves: &[Move]) -> Result<Game, Error> {
+        let mut game = Game::new();
+        for &mv in moves {
+            game.play(mv)?;
+        }
+        Ok(game)
+    }
+
+    /// Creates a new game and plays the space separated moves in UCI long algebraic
+    /// notation, as returned by [Game::moves_to_string].
+    /// Returns [Error::InvalidMove] with the index of the first move that can't be
+    /// parsed or is not legal.
+    pub fn from_moves_string(moves: &str) -> Result<Game, Error> {
+        let moves = moves.split_whitespace()
+            .enumerate()
+            .map(|(i, uci)| Move::from_uci(uci).ok_or(Error::InvalidMove(i)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Game::replay(&moves)
+    }
+
+    /// Returns the moves played so far as space separated moves in UCI long algebraic
+    /// notation, e.g. `"e2e4 e7e5 g1f3"`. The start position is not included.
+    pub fn moves_to_string(&self) -> String {
+        self.history.iter()
+            .map(Move::to_uci)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns the start position in Forsyth-Edwards Notation if the game was created
+    /// with [Game::from_fen] or set up with [Game::set_to_move], or `None` if it
+    /// started from the standard initial position.
+    pub fn start_fen(&self) -> Option<&str> {
+        self.started_from_fen.as_deref()
+    }
+
+    /// Returns the game in Portable Game Notation. Unknown tags are set to `"?"`,
+    /// finished games include the `Termination` tag and drawn games a comment
+    /// saying why, and games not started from the standard initial position
+    /// include the `SetUp` and `FEN` tags.
+    pub fn to_pgn(&self) -> String {
+
+        let result = match self.result() {
+            Some(GameResult::Win(Player::White)) => "1-0",
+            Some(GameResult::Win(Player::Black)) => "0-1",
+            Some(GameResult::Draw) => "1/2-1/2",
+            None => "*",
+        };
+
+        let mut pgn = String::new();
+        for tag in ["Event", "Site", "Date", "Round", "White", "Black"] {
+            pgn += &format!("[{} \"?\"]\n", tag);
+        }
+        pgn += &format!("[Result \"{}\"]\n", result);
+        match self.state {
+            State::TimeOut => pgn += "[Termination \"time forfeit\"]\n",
+            _ if self.result().is_some() => pgn += "[Termination \"normal\"]\n",
+            _ => (),
+        }
+        if let Some(fen) = self.start_fen() {
+            pgn += &format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", fen);
+        }
+        pgn += "\n";
+
+        let mut number = self.start.fullmove_number();
+        let mut player = self.start.player;
+        for (i, san) in self.san_iter().enumerate() {
+            match player {
+                Player::White => pgn += &format!("{}. ", number),
+                Player::Black if i == 0 => pgn += &format!("{}... ", number),
+                Player::Black => (),
+            }
+            pgn += &san;
+            pgn += " ";
+            if player == Player::Black {
+                number += 1;
+            }
+            player = player.opponent();
+        }
+
+        if let Some(reason) = self.draw_reason {
+            let reason = match reason {
+                DrawReason::Stalemate => "stalemate",
+                DrawReason::FiftyMove => "the fifty-move rule",
+                DrawReason::Threefold => "threefold repetition",
+                DrawReason::InsufficientMaterial => "insufficient material",
+                DrawReason::Agreement => "agreement",
+            };
+            pgn += &format!("{{Drawn by {}}} ", reason);
+        }
+
+        pgn += result;
+        pgn
+    }
+
+    /// Resets the game to its initial state, keeping its options and
+    /// restoring the clock to its starting time.
     pub fn reset(&mut self) {
-        *self = Game::new();
+        let clock = self.start_clock;
+        *self = Game::with_options(self.options.clone());
+        self.clock = clock;
+        self.start_clock = clock;
+    }
+
+    /// Parses a position in algebraic notation, e.g. `"e4"`, into (x, y) coordinates.
+    /// Returns [Error::InvalidPosition] if the string is not a position on the board.
+    pub fn square_from_algebraic(s: &str) -> Result<(u8, u8), Error> {
+        utils::parse_square(s).ok_or(Error::InvalidPosition)
+    }
+
+    /// Returns the position at (x, y) in algebraic notation, e.g. `"e4"`.
+    /// Returns [Error::InvalidPosition] if position is not on the board.
+    pub fn square_to_algebraic(x: u8, y: u8) -> Result<String, Error> {
+        if !valid_pos(x, y) {
+            return Err(Error::InvalidPosition);
+        }
+        Ok(utils::square_name(x, y))
     }
 
     /// Returns the state of the game.
@@ -64,6 +389,110 @@ impl Game {
         self.board.player
     }
 
+    /// Returns the result of the game, or `None` if it is not over.
+    pub fn result(&self) -> Option<GameResult> {
+        let opponent = self.board.player.opponent();
+        match self.state {
+            State::CheckMate | State::TimeOut => Some(GameResult::Win(opponent)),
+            State::Stalemate | State::Draw => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+
+    /// Returns why the game was drawn, or `None` if it is not drawn.
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        self.draw_reason
+    }
+
+    /// Returns true if the current player is in check and has no legal moves.
+    pub fn is_checkmate(&self) -> bool {
+        self.board.is_in_check() && self.board.legal_move_count() == 0
+    }
+
+    /// Returns true if the current player is not in check but has no legal moves.
+    pub fn is_stalemate(&self) -> bool {
+        !self.board.is_in_check() && self.board.legal_move_count() == 0
+    }
+
+    /// Returns the clock, if the game is played with one.
+    pub fn clock(&self) -> Option<Clock> {
+        self.clock
+    }
+
+    /// Charges `elapsed_ms` to the clock of the current player. If the clock
+    /// runs out, transitions state to [State::TimeOut].
+    /// Does nothing if the game has no clock or is over.
+    pub fn tick(&mut self, elapsed_ms: u64) {
+
+        if self.result().is_some() {
+            return;
+        }
+
+        if let Some(clock) = &mut self.clock {
+            let remaining = clock.remaining_mut(self.board.player);
+            *remaining = remaining.saturating_sub(elapsed_ms);
+            if *remaining == 0 {
+                self.state = State::TimeOut;
+            }
+        }
+    }
+
+    /// Offers a draw on behalf of `by`, replacing any previous offer.
+    /// The offer is withdrawn when `by` plays a move.
+    /// Does nothing if the game is over.
+    pub fn offer_draw(&mut self, by: Player) {
+        if self.result().is_none() {
+            self.draw_offer = Some(by);
+        }
+    }
+
+    /// Returns the player that has offered a draw, if any.
+    pub fn draw_offer(&self) -> Option<Player> {
+        self.draw_offer
+    }
+
+    /// Responds to the current draw offer. If accepted, transitions state
+    /// to [State::Draw], otherwise the offer is cleared.
+    /// Does nothing if no draw has been offered.
+    pub fn respond_draw(&mut self, accept: bool) {
+        if self.draw_offer.take().is_some() && accept && self.result().is_none() {
+            self.state = State::Draw;
+            self.draw_reason = Some(DrawReason::Agreement);
+        }
+    }
+
+    /// Claims a draw by the fifty-move rule or threefold repetition, and
+    /// transitions state to [State::Draw].
+    /// Returns [Error::NoDrawToClaim] if neither applies.
+    /// Returns [Error::InvalidState] if the game is over or state is [State::SelectPromotion].
+    pub fn claim_draw(&mut self) -> Result<(), Error> {
+
+        if !matches!(self.state, State::SelectPiece | State::SelectMove) {
+            return Err(Error::InvalidState);
+        }
+
+        let reason = if self.board.halfmove_clock() >= 100 {
+            DrawReason::FiftyMove
+        } else if self.repetition_count() >= 3 {
+            DrawReason::Threefold
+        } else {
+            return Err(Error::NoDrawToClaim);
+        };
+
+        self.state = State::Draw;
+        self.draw_reason = Some(reason);
+
+        Ok(())
+    }
+
+    /// Returns the number of times the current position has occurred in the game,
+    /// including now. Positions are compared by [Board::zobrist_hash].
+    pub fn repetition_count(&self) -> u8 {
+        // Current position is last
+        let current = self.hashes[self.hashes.len() - 1];
+        self.hashes.iter().filter(|&&hash| hash == current).count() as u8
+    }
+
     /// Returns black pieces and their positions
     pub fn get_black_positions(&self) -> &[(Piece, u8, u8)] {
         &self.black_positions[..]
@@ -74,6 +503,136 @@ impl Game {
         &self.white_positions[..]
     }
 
+    /// Returns pieces of both players, with positions transformed so that
+    /// `viewpoint`'s pieces are at the bottom of the board, as seen by that player.
+    /// For [Player::White] positions are unchanged, and for [Player::Black]
+    /// the board is rotated half a turn.
+    pub fn get_positions_oriented(&self, viewpoint: Player) -> Vec<(Piece, Player, u8, u8)> {
+        self.board.pieces()
+            .map(|(piece, player, x, y)| match viewpoint {
+                Player::White => (piece, player, x, y),
+                Player::Black => (piece, player, 7 - x, 7 - y),
+            })
+            .collect()
+    }
+
+    /// Returns all moves played so far, in order.
+    pub fn history(&self) -> &[Move] {
+        &self.history[..]
+    }
+
+    /// Returns the number of half moves played.
+    pub fn ply(&self) -> u32 {
+        self.history.len() as u32
+    }
+
+    /// Returns the most recently played move, or `None` if no move has been played.
+    pub fn last_move(&self) -> Option<Move> {
+        self.history.last().copied()
+    }
+
+    /// Returns the positions the piece moved by the last move could move to, as if
+    /// it was its owner's turn. Empty if no move has been played.
+    pub fn last_moved_piece_moves(&self) -> Vec<(u8, u8)> {
+
+        let to = match self.history.last() {
+            Some(mv) => mv.to,
+            None => return Vec::new(),
+        };
+
+        let owner = self.board.player.opponent();
+
+        self.board.full_move_map()
+            .into_iter()
+            .find(|&(_, player, pos, _)| player == owner && pos == to)
+            .map(|(_, _, _, moves)| moves)
+            .unwrap_or_default()
+    }
+
+    /// Returns the player and promoted piece of every promotion played so far, in order.
+    pub fn promotions_played(&self) -> Vec<(Player, Piece)> {
+
+        let mut player = self.start.player;
+        let mut promotions = Vec::new();
+
+        for mv in &self.history {
+            if let (true, Some(piece)) = (mv.kind.is_promotion(), mv.promotion) {
+                promotions.push((player, piece));
+            }
+            player = player.opponent();
+        }
+
+        promotions
+    }
+
+    /// Returns `Some(true)` if the last move played was castling kingside, `Some(false)`
+    /// if it was castling queenside, and `None` otherwise or if no move has been played.
+    pub fn last_move_was_castling(&self) -> Option<bool> {
+        match self.history.last()?.kind {
+            MoveKind::KingsideCastle => Some(true),
+            MoveKind::QueensideCastle => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the last move played put the current player in check.
+    pub fn last_move_gave_check(&self) -> bool {
+        self.last_move_gave_check
+    }
+
+    /// Returns an iterator over the moves played so far in Standard Algebraic Notation.
+    /// Moves are converted lazily by replaying them from the start position.
+    pub fn san_iter(&self) -> impl Iterator<Item = String> + '_ {
+
+        let mut board = self.start.clone();
+        board.set_castling_enabled(self.options.castling_enabled);
+
+        self.history.iter().map(move |mv| {
+            let san = board.san(mv);
+            board = board.after_move(mv);
+            san
+        })
+    }
+
+    /// Returns all moves played so far in Standard Algebraic Notation, in order.
+    pub fn san_history(&self) -> Vec<String> {
+        self.san_iter().collect()
+    }
+
+    /// Returns the id of the piece at `x`, `y` of either player, or `None` if the
+    /// position is empty. The id follows the piece as it moves, so frontends may use
+    /// it to animate pieces, see [Board::pieces_with_ids].
+    pub fn piece_id_at(&self, x: u8, y: u8) -> Option<usize> {
+        self.board.pieces_with_ids()
+            .find(|&(_, _, _, px, py)| (px, py) == (x, y))
+            .map(|(id, ..)| id)
+    }
+
+    /// Returns the board of the current position.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns the current position in Forsyth-Edwards Notation.
+    pub fn to_fen(&self) -> String {
+        self.board.to_fen()
+    }
+
+    /// Returns true if the current position matches `fen`.
+    /// Whitespace is normalized, and the move counters are only
+    /// compared if `fen` includes them.
+    pub fn position_eq_fen(&self, fen: &str) -> bool {
+
+        let own = self.to_fen();
+        let own = own.split_whitespace().collect::<Vec<_>>();
+        let other = fen.split_whitespace().collect::<Vec<_>>();
+
+        match other.len() {
+            4 => own[..4] == other[..],
+            _ => own == other,
+        }
+    }
+
     /// Selects a piece by position on the board.
     /// If position is occupied by the current player, transitions state to [State::SelectMove].
     /// If position is empty or occupied by opponent, does nothing.
@@ -104,7 +663,7 @@ impl Game {
                         m => {
                             self.selected_moves.0 = m;
                             self.selected_moves.1 = utils::BitIterator::new(m)
-                                                    .map(|x| utils::unflatten_bit(x))
+                                                    .map(utils::unflatten_bit)
                                                     .collect::<Vec<_>>();
                         }
                     };
@@ -113,6 +672,33 @@ impl Game {
         Ok(())
     }
 
+    /// Same as [Game::select_piece], with the position in algebraic notation, e.g. `"e2"`.
+    /// Returns [Error::InvalidPosition] if the string is not a position on the board.
+    pub fn select_piece_sq(&mut self, sq: &str) -> Result<(), Error> {
+        let (x, y) = Game::square_from_algebraic(sq)?;
+        self.select_piece(x, y)
+    }
+
+    /// Same as [Game::select_piece], but tells whether a piece was selected,
+    /// and if not, why.
+    pub fn try_select_piece(&mut self, x: u8, y: u8) -> Result<SelectOutcome, Error> {
+
+        self.select_piece(x, y)?;
+
+        let pos = utils::flatten_bit(x, y);
+
+        Ok(if matches!(self.state, State::SelectMove) {
+            match self.selected_moves.0 {
+                0 => SelectOutcome::NoLegalMoves,
+                _ => SelectOutcome::Selected,
+            }
+        } else if self.board.occupied() & pos > 0 {
+            SelectOutcome::OpponentPiece
+        } else {
+            SelectOutcome::EmptySquare
+        })
+    }
+
     /// Returns positions corresponding to the legal moves for piece selected with
     /// [Game::select_piece]. Can be empty slice.
     /// Returns [Error::InvalidState] if game state is not [State::SelectMove].
@@ -125,6 +711,82 @@ impl Game {
         Ok(&self.selected_moves.1[..])
     }
 
+    /// Returns positions of the current player's pieces that have at least one legal move.
+    /// Empty if game state is not [State::SelectPiece] or [State::SelectMove].
+    pub fn movable_pieces(&self) -> Vec<(u8, u8)> {
+
+        if !matches!(self.state, State::SelectPiece | State::SelectMove) {
+            return Vec::new();
+        }
+
+        let positions = match self.board.player {
+            Player::White => &self.white_positions,
+            Player::Black => &self.black_positions,
+        };
+
+        positions.iter()
+            .map(|&(_, x, y)| (x, y))
+            .filter(|&(x, y)| match self.board.id_from_pos(x, y) {
+                Some(id) => self.board.get_legal_moves(id) != 0,
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Returns the positions of the current player's pieces mapped to the destination
+    /// positions of their legal moves. Pieces without legal moves are left out.
+    /// Empty if game state is not [State::SelectPiece] or [State::SelectMove].
+    pub fn legal_move_map(&self) -> HashMap<(u8, u8), Vec<(u8, u8)>> {
+
+        if !matches!(self.state, State::SelectPiece | State::SelectMove) {
+            return HashMap::new();
+        }
+
+        let positions = match self.board.player {
+            Player::White => &self.white_positions,
+            Player::Black => &self.black_positions,
+        };
+
+        positions.iter()
+            .filter_map(|&(_, x, y)| {
+                let id = self.board.id_from_pos(x, y)?;
+                match self.board.get_legal_moves(id) {
+                    0 => None,
+                    m => Some(((x, y), utils::BitIterator::new(m).map(utils::unflatten_bit).collect())),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the legal moves of the current player's piece at `x`, `y`, with
+    /// [Move::kind] set. A pawn move to the last rank yields one move per
+    /// piece in [Game::promotion_choices]. Returns an empty list if there is no
+    /// such piece, or the game state is not [State::SelectPiece] or [State::SelectMove].
+    pub fn moves_for_expanded(&self, x: u8, y: u8) -> Vec<Move> {
+
+        if !matches!(self.state, State::SelectPiece | State::SelectMove) || !valid_pos(x, y) {
+            return Vec::new();
+        }
+
+        self.board.legal_move_list()
+            .into_iter()
+            .filter(|mv| mv.from == (x, y))
+            .filter(|mv| mv.promotion.is_none_or(|piece| self.is_allowed_promotion(piece)))
+            .collect()
+    }
+
+    /// Returns mask of the legal moves for piece selected with [Game::select_piece],
+    /// with bit `x + 8 * y` set for each destination position.
+    /// Returns [Error::InvalidState] if game state is not [State::SelectMove].
+    pub fn selected_moves_mask(&self) -> Result<u64, Error> {
+
+        if !matches!(self.state, State::SelectMove) {
+            return Err(Error::InvalidState);
+        }
+
+        Ok(self.selected_moves.0)
+    }
+
     /// Returns position of currently selected piece.
     /// Returns [Error::InvalidState] if game state is not [State::SelectMove].
     pub fn get_selected_pos(&self) -> Result<(u8, u8), Error> {
@@ -136,6 +798,33 @@ impl Game {
         Ok(self.selected_pos)
     }
 
+    /// Returns true if moving to position captures a piece. If a piece is selected,
+    /// this takes into account that only pawns capture en passant.
+    /// Returns false if position is not on the board.
+    pub fn move_is_capture(&self, x: u8, y: u8) -> bool {
+
+        if !valid_pos(x, y) {
+            return false;
+        }
+
+        let dest = utils::flatten_bit(x, y);
+
+        if matches!(self.state, State::SelectMove) && dest & self.selected_moves.0 > 0 {
+            self.board.move_kind(self.selected_id, dest).is_capture()
+        } else {
+            self.board.is_capture(dest)
+        }
+    }
+
+    /// Returns true if the current player has a pawn at `x`, `y` that may promote
+    /// with its next move. Returns false if the position is not on the board, or
+    /// the game state is not [State::SelectPiece] or [State::SelectMove].
+    pub fn pawn_can_promote(&self, x: u8, y: u8) -> bool {
+        matches!(self.state, State::SelectPiece | State::SelectMove)
+            && valid_pos(x, y)
+            && self.board.pawn_can_promote(x, y)
+    }
+
     /// Selects a move by corresponding position and executes it.
     /// If position does not correspond to a legal move, reverts state
     /// back to [State::SelectPiece].
@@ -154,26 +843,52 @@ impl Game {
         let dest = utils::flatten_bit(x, y);
 
         if dest & self.selected_moves.0 > 0 {
-            self.board.play_move(self.selected_id, dest);
-        }
 
-        self.state = State::SelectPiece;
+            self.commit_move(self.board.player);
 
-        self.update_positions();
+            let kind = self.board.move_kind(self.selected_id, dest);
+            self.board.play_move(self.selected_id, dest);
 
-        if self.board.has_promotion() {
-            self.state = State::SelectPromotion;
-        } else if self.board.is_checkmate() {
-            self.state = State::CheckMate;
+            let mut promotion = None;
+            if self.options.auto_queen
+                && self.board.has_promotion()
+                && self.is_allowed_promotion(Piece::Queen)
+            {
+                self.board.select_promotion(Piece::Queen);
+                promotion = Some(Piece::Queen);
+            }
+
+            self.history.push(Move {
+                from: self.selected_pos,
+                to: (x, y),
+                promotion,
+                kind,
+            });
         }
 
+        self.update_state();
+
         Ok(())
     }
 
+    /// Same as [Game::select_move], with the position in algebraic notation, e.g. `"e4"`.
+    /// Returns [Error::InvalidPosition] if the string is not a position on the board.
+    pub fn select_move_sq(&mut self, sq: &str) -> Result<(), Error> {
+        let (x, y) = Game::square_from_algebraic(sq)?;
+        self.select_move(x, y)
+    }
+
+    /// Returns the pieces pawns may be promoted to, see [GameOptions::allowed_promotions].
+    pub fn promotion_choices(&self) -> Vec<Piece> {
+        use Piece::*;
+        [Queen, Rook, Bishop, Knight].into_iter()
+            .filter(|&piece| self.is_allowed_promotion(piece))
+            .collect()
+    }
+
     /// Selects a piece to promote pawn to.
-    /// Must bee one of [Piece::Rook], [Piece::Knight], [Piece::Bishop]
-    /// or [Piece::Queen]. Returns [Error::InvalidPromotion] if any other
-    /// piece is provided. Returns [Error::InvalidState] if state is not
+    /// Must bee one of [Game::promotion_choices]. Returns [Error::InvalidPromotion]
+    /// if any other piece is provided. Returns [Error::InvalidState] if state is not
     /// [State::SelectPromotion].
     pub fn select_promotion(&mut self, piece: Piece) -> Result<(), Error> {
 
@@ -181,28 +896,1259 @@ impl Game {
             return Err(Error::InvalidState);
         }
 
-        use Piece::*;
-
-        match piece {
-            Rook | Knight | Bishop | Queen => (), 
-            _ => return Err(Error::InvalidPromotion),
-        };
+        if !self.is_allowed_promotion(piece) {
+            return Err(Error::InvalidPromotion);
+        }
 
         self.board.select_promotion(piece);
 
-        self.state = State::SelectPiece;
-        self.update_positions();
+        if let Some(mv) = self.history.last_mut() {
+            mv.promotion = Some(piece);
+        }
+
+        // The promoted piece may give checkmate or stalemate
+        self.update_state();
 
         Ok(())
     }
 
-    fn update_positions(&mut self) {
-        self.black_positions = self.board.black_iter().collect();
-        self.white_positions = self.board.white_iter().collect();
+    /// Plays space separated moves in Standard Algebraic Notation, e.g.
+    /// `"1. e4 e5 2. Nf3 Nc6"`. Move numbers and a final result are ignored.
+    /// Stops at the first move that is not legal and returns [Error::InvalidSan]
+    /// with the index of its token in `line`. Moves before it remain played.
+    pub fn play_san_line(&mut self, line: &str) -> Result<(), Error> {
+
+        for (i, token) in line.split_whitespace().enumerate() {
+
+            if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                continue;
+            }
+
+            // Move numbers may be attached to the move, e.g. "1.e4" or "3...Nf6"
+            let token = token.rsplit('.').next().unwrap_or(token);
+            if token.is_empty() {
+                continue;
+            }
+
+            let mv = self.board.parse_san(token).ok_or(Error::InvalidSan(i))?;
+            self.apply_move(mv).map_err(|_| Error::InvalidSan(i))?;
+        }
+
+        Ok(())
     }
 
-}
+    /// Plays a move for the current player. If the move promotes a pawn,
+    /// [Move::promotion] is applied, or the first of [Game::promotion_choices] if it is `None`.
+    /// The promotion must be one of [Game::promotion_choices].
+    /// [Move::kind] is ignored.
+    /// Returns [Error::InvalidMove] if the move is not legal.
+    pub fn apply_move(&mut self, mv: Move) -> Result<(), Error> {
 
-fn valid_pos(x: u8, y: u8) -> bool {
-    x < 8 && y < 8
+        let mut mv = mv;
+
+        if mv.promotion.is_none() && valid_pos(mv.from.0, mv.from.1) && valid_pos(mv.to.0, mv.to.1) {
+            if let Some(id) = self.board.id_from_pos(mv.from.0, mv.from.1) {
+                let dest = utils::flatten_bit(mv.to.0, mv.to.1);
+                if dest & self.board.get_legal_moves(id) > 0
+                    && self.board.move_kind(id, dest).is_promotion()
+                {
+                    mv.promotion = Some(self.promotion_choices()[0]);
+                }
+            }
+        }
+
+        let player = self.board.player;
+        self.play(mv)?;
+        self.commit_move(player);
+
+        Ok(())
+    }
+
+    /// Sets the player to move, e.g. when setting up a puzzle, and updates the
+    /// game state for the new player. The current position becomes the start
+    /// of the game, and played moves are discarded.
+    /// Returns [Error::InvalidState] if game state is [State::SelectPromotion], or if
+    /// the game ended on time or by an agreed draw.
+    /// Returns [Error::IllegalPosition] if the player not to move would be in check.
+    pub fn set_to_move(&mut self, player: Player) -> Result<(), Error> {
+
+        if matches!(self.state, State::SelectPromotion) || self.ended_off_board() {
+            return Err(Error::InvalidState);
+        }
+
+        let mut board = self.board.clone();
+        board.set_to_move(player);
+
+        if !board.is_legal_position() {
+            return Err(Error::IllegalPosition);
+        }
+
+        self.history.clear();
+        self.redo.clear();
+        self.draw_offer = None;
+        self.set_board(board);
+        self.start = self.board.clone();
+        self.started_from_fen = self.custom_start_fen();
+
+        Ok(())
+    }
+
+    /// Passes the turn to the opponent without moving, as with [Game::set_to_move].
+    /// Any en passant capture is no longer available. The current position becomes
+    /// the start of the game, so moves played before can no longer be undone.
+    /// Returns [Error::InvalidState] if the current player is in check, or if game
+    /// state is not [State::SelectPiece] or [State::SelectMove].
+    pub fn pass_turn(&mut self) -> Result<(), Error> {
+
+        if !matches!(self.state, State::SelectPiece | State::SelectMove)
+            || self.board.null_move().is_none()
+        {
+            return Err(Error::InvalidState);
+        }
+
+        self.set_to_move(self.board.player.opponent())
+    }
+
+    /// Takes back the last move. It can be played again with [Game::redo]
+    /// until a new move is played.
+    /// Returns [Error::NothingToUndo] if no move has been played.
+    /// Returns [Error::InvalidState] if game state is [State::SelectPromotion], or if
+    /// the game ended on time or by an agreed draw.
+    pub fn undo(&mut self) -> Result<(), Error> {
+        match self.ply() {
+            0 => Err(Error::NothingToUndo),
+            n => self.goto_ply(n - 1),
+        }
+    }
+
+    /// Plays the last move taken back with [Game::undo].
+    /// Returns [Error::NothingToRedo] if there is no such move.
+    /// Returns [Error::InvalidState] if game state is [State::SelectPromotion], or if
+    /// the game ended on time or by an agreed draw.
+    pub fn redo(&mut self) -> Result<(), Error> {
+        if self.redo.is_empty() {
+            return Err(Error::NothingToRedo);
+        }
+        self.goto_ply(self.ply() + 1)
+    }
+
+    /// Returns to the position before the first move.
+    /// Played moves are kept, and can be stepped through with [Game::goto_ply]
+    /// until a new move is played.
+    /// Returns [Error::InvalidState] if game state is [State::SelectPromotion], or if
+    /// the game ended on time or by an agreed draw.
+    pub fn goto_start(&mut self) -> Result<(), Error> {
+        self.goto_ply(0)
+    }
+
+    /// Goes to the position after the first `n` half moves of the game, including
+    /// moves stepped back over with [Game::goto_start] or [Game::goto_ply].
+    /// Returns [Error::InvalidPly] if fewer than `n` moves are available.
+    /// Returns [Error::InvalidState] if game state is [State::SelectPromotion], or if
+    /// the game ended on time or by an agreed draw.
+    pub fn goto_ply(&mut self, n: u32) -> Result<(), Error> {
+
+        if matches!(self.state, State::SelectPromotion) || self.ended_off_board() {
+            return Err(Error::InvalidState);
+        }
+
+        let n = n as usize;
+
+        if n > self.history.len() + self.redo.len() {
+            return Err(Error::InvalidPly);
+        }
+
+        if n < self.history.len() {
+
+            let undone = self.history.split_off(n);
+            self.redo.extend(undone.into_iter().rev());
+
+            // Replay remaining moves from the start
+            let history = std::mem::take(&mut self.history);
+            self.set_board(self.start.clone());
+            for mv in history {
+                self.play(mv)?;
+            }
+        }
+
+        while self.history.len() < n {
+            // Number of moves was checked above
+            let mv = self.redo.pop().unwrap();
+            self.play(mv)?;
+        }
+
+        Ok(())
+    }
+
+    // Plays a complete move, including promotion
+    fn play(&mut self, mv: Move) -> Result<(), Error> {
+
+        let err = Error::InvalidMove(self.history.len());
+
+        if !matches!(self.state, State::SelectPiece | State::SelectMove)
+            || !valid_pos(mv.from.0, mv.from.1)
+            || !valid_pos(mv.to.0, mv.to.1)
+        {
+            return Err(err);
+        }
+
+        let id = self.board.id_from_pos(mv.from.0, mv.from.1).ok_or(err)?;
+        let dest = utils::flatten_bit(mv.to.0, mv.to.1);
+
+        if dest & self.board.get_legal_moves(id) == 0 {
+            return Err(err);
+        }
+
+        let kind = self.board.move_kind(id, dest);
+
+        // Play on a copy so that the game is untouched if promotion is invalid
+        let mut board = self.board.clone();
+        board.play_move(id, dest);
+
+        match (board.has_promotion(), mv.promotion) {
+            (true, Some(piece)) if self.is_allowed_promotion(piece) => board.select_promotion(piece),
+            (false, None) => (),
+            _ => return Err(err),
+        };
+
+        self.board = board;
+        self.history.push(Move { kind, ..mv });
+        self.update_state();
+
+        Ok(())
+    }
+
+    // FEN of the start position, or None if it is the standard initial position
+    fn custom_start_fen(&self) -> Option<String> {
+        let fen = self.start.to_fen();
+        (fen != Board::new().to_fen()).then_some(fen)
+    }
+
+    // Updates the clock, draw offer and redo moves for a new move by `player`
+    fn commit_move(&mut self, player: Player) {
+
+        if let Some(clock) = &mut self.clock {
+            *clock.remaining_mut(player) += clock.increment_ms;
+        }
+
+        if self.draw_offer == Some(player) {
+            self.draw_offer = None;
+        }
+
+        self.redo.clear();
+    }
+
+    // True if the game ended on time or by agreement, which changing the
+    // position would silently discard
+    fn ended_off_board(&self) -> bool {
+        matches!(self.state, State::TimeOut)
+            || matches!(self.draw_reason, Some(DrawReason::Agreement))
+    }
+
+    fn is_allowed_promotion(&self, piece: Piece) -> bool {
+        use Piece::*;
+        matches!(piece, Rook | Knight | Bishop | Queen)
+            && self.options.allowed_promotions.contains(&piece)
+    }
+
+    // Replaces the board, applying game options
+    fn set_board(&mut self, board: Board) {
+        self.board = board;
+        self.board.set_castling_enabled(self.options.castling_enabled);
+        self.update_state();
+    }
+
+    // Updates state after a move has been played
+    fn update_state(&mut self) {
+
+        self.state = State::SelectPiece;
+        self.draw_reason = None;
+        self.last_move_gave_check = !self.history.is_empty() && self.board.is_in_check();
+
+        // Replaces the hash of the position before promotion, or of undone moves
+        self.hashes.truncate(self.history.len());
+        self.hashes.push(self.board.zobrist_hash());
+
+        self.update_positions();
+
+        if self.board.has_promotion() {
+            self.state = State::SelectPromotion;
+            return;
+        }
+
+        match self.board.outcome() {
+            Some(Outcome::Checkmate) => self.state = State::CheckMate,
+            Some(Outcome::Stalemate) => {
+                self.state = State::Stalemate;
+                self.draw_reason = Some(DrawReason::Stalemate);
+            },
+            Some(Outcome::InsufficientMaterial) => {
+                self.state = State::Draw;
+                self.draw_reason = Some(DrawReason::InsufficientMaterial);
+            },
+            None => (),
+        }
+    }
+
+    fn update_positions(&mut self) {
+        self.black_positions = self.board.black_iter().collect();
+        self.white_positions = self.board.white_iter().collect();
+    }
+
+}
+
+fn valid_pos(x: u8, y: u8) -> bool {
+    x < 8 && y < 8
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::game::*;
+    use crate::chess_move::MoveKind;
+
+    fn play(game: &mut Game, from: (u8, u8), to: (u8, u8)) {
+        game.select_piece(from.0, from.1).unwrap();
+        game.select_move(to.0, to.1).unwrap();
+    }
+
+    #[test]
+    fn ply() {
+        let mut game = Game::new();
+        assert_eq!(game.ply(), 0);
+        play(&mut game, (4, 1), (4, 3)); // e4
+        play(&mut game, (4, 6), (4, 4)); // e5
+        assert_eq!(game.ply(), 2);
+
+        // Illegal moves are not counted
+        play(&mut game, (4, 3), (4, 4));
+        assert_eq!(game.ply(), 2);
+    }
+
+    #[test]
+    fn goto_ply() {
+        let mut game = Game::new();
+        play(&mut game, (4, 1), (4, 3)); // e4
+        play(&mut game, (4, 6), (4, 4)); // e5
+        let fen = game.to_fen();
+        play(&mut game, (6, 0), (5, 2)); // Nf3
+        let history = game.history().to_vec();
+
+        game.goto_start().unwrap();
+        assert_eq!(game.to_fen(), Game::new().to_fen());
+        assert_eq!(game.ply(), 0);
+
+        game.goto_ply(2).unwrap();
+        assert_eq!(game.to_fen(), fen);
+
+        game.goto_ply(3).unwrap();
+        assert_eq!(game.history(), &history[..]);
+        assert_eq!(game.goto_ply(4), Err(Error::InvalidPly));
+
+        // Playing a new move discards moves after it
+        game.goto_ply(1).unwrap();
+        play(&mut game, (3, 6), (3, 4)); // d5
+        assert_eq!(game.goto_ply(3), Err(Error::InvalidPly));
+        assert_eq!(game.ply(), 2);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut game = Game::new();
+        assert_eq!(game.undo(), Err(Error::NothingToUndo));
+        assert_eq!(game.redo(), Err(Error::NothingToRedo));
+
+        play(&mut game, (4, 1), (4, 3)); // e4
+        play(&mut game, (4, 6), (4, 4)); // e5
+        let fen = game.to_fen();
+
+        game.undo().unwrap();
+        assert_eq!(game.ply(), 1);
+        assert!(matches!(game.get_current_player(), Player::Black));
+
+        game.redo().unwrap();
+        assert_eq!(game.to_fen(), fen);
+        assert_eq!(game.redo(), Err(Error::NothingToRedo));
+
+        // Playing a new move clears moves to redo
+        game.undo().unwrap();
+        play(&mut game, (3, 6), (3, 4)); // d5
+        assert_eq!(game.redo(), Err(Error::NothingToRedo));
+    }
+
+    #[test]
+    fn last_move() {
+        let mut game = Game::new();
+        assert_eq!(game.last_move(), None);
+        play(&mut game, (4, 1), (4, 3)); // e4
+        let mv = game.last_move().unwrap();
+        assert_eq!(mv, Move::new((4, 1), (4, 3), None));
+        assert_eq!(mv.kind, MoveKind::DoublePush);
+    }
+
+    #[test]
+    fn position_eq_fen() {
+        let mut game = Game::new();
+        play(&mut game, (4, 1), (4, 3)); // e4
+        assert!(game.position_eq_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"));
+        assert!(game.position_eq_fen("  rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR  b KQkq -"));
+        assert!(!game.position_eq_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2"));
+        assert!(!game.position_eq_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -"));
+    }
+
+    // Plays moves until white captures on a8 and promotes
+    fn play_to_promotion(game: &mut Game) {
+        play(game, (0, 1), (0, 3)); // a4
+        play(game, (1, 6), (1, 4)); // b5
+        play(game, (0, 3), (1, 4)); // axb5
+        play(game, (0, 6), (0, 5)); // a6
+        play(game, (1, 4), (0, 5)); // bxa6
+        play(game, (2, 7), (1, 6)); // Bb7
+        play(game, (0, 5), (1, 6)); // axb7
+        play(game, (1, 7), (2, 5)); // Nc6
+        play(game, (1, 6), (0, 7)); // bxa8
+    }
+
+    #[test]
+    fn auto_queen() {
+        let mut game = Game::with_options(GameOptions { auto_queen: true, ..Default::default() });
+        play_to_promotion(&mut game);
+
+        assert!(matches!(game.get_state(), State::SelectPiece));
+        assert!(matches!(game.get_current_player(), Player::Black));
+        assert!(game.get_white_positions().contains(&(Piece::Queen, 0, 7)));
+        assert_eq!(game.last_move().unwrap().promotion, Some(Piece::Queen));
+
+        let mut game = Game::new();
+        play_to_promotion(&mut game);
+        assert!(matches!(game.get_state(), State::SelectPromotion));
+    }
+
+    #[test]
+    fn allowed_promotions() {
+        let options = GameOptions {
+            allowed_promotions: vec![Piece::Queen, Piece::Knight],
+            ..Default::default()
+        };
+        let mut game = Game::with_options(options.clone());
+        assert_eq!(game.promotion_choices(), [Piece::Queen, Piece::Knight]);
+        assert_eq!(Game::new().promotion_choices().len(), 4);
+
+        play_to_promotion(&mut game);
+        assert_eq!(game.select_promotion(Piece::Rook), Err(Error::InvalidPromotion));
+        assert_eq!(game.select_promotion(Piece::King), Err(Error::InvalidPromotion));
+        assert!(matches!(game.get_state(), State::SelectPromotion));
+        game.select_promotion(Piece::Knight).unwrap();
+        assert!(game.get_white_positions().contains(&(Piece::Knight, 0, 7)));
+
+        // Replaying a disallowed promotion fails
+        let mut moves = game.history().to_vec();
+        moves.last_mut().unwrap().promotion = Some(Piece::Bishop);
+        let mut game = Game::with_options(options);
+        let last = moves.pop().unwrap();
+        for mv in moves {
+            game.apply_move(mv).unwrap();
+        }
+        assert!(matches!(game.apply_move(last), Err(Error::InvalidMove(_))));
+
+        // Expanded moves only include allowed promotions, and apply_move defaults to the first
+        let options = GameOptions {
+            allowed_promotions: vec![Piece::Knight, Piece::Rook],
+            ..Default::default()
+        };
+        let mut moves = {
+            let mut game = Game::new();
+            play_to_promotion(&mut game);
+            game.select_promotion(Piece::Queen).unwrap();
+            game.history().to_vec()
+        };
+        let last = moves.pop().unwrap();
+        let mut game = Game::with_options(options);
+        for mv in moves {
+            game.apply_move(mv).unwrap();
+        }
+        let promotions = game.moves_for_expanded(1, 6)
+            .into_iter()
+            .filter(|mv| mv.to == (0, 7))
+            .map(|mv| mv.promotion)
+            .collect::<Vec<_>>();
+        assert_eq!(promotions, [Some(Piece::Rook), Some(Piece::Knight)]);
+        game.apply_move(Move { promotion: None, ..last }).unwrap();
+        assert_eq!(game.last_move().unwrap().promotion, Some(Piece::Rook));
+
+        // Without any allowed piece, all are allowed
+        for allowed_promotions in [vec![], vec![Piece::King]] {
+            let options = GameOptions { allowed_promotions, ..Default::default() };
+            let mut game = Game::with_options(options);
+            assert_eq!(game.promotion_choices().len(), 4);
+            play_to_promotion(&mut game);
+            game.select_promotion(Piece::Queen).unwrap();
+        }
+    }
+
+    #[test]
+    fn castling_disabled() {
+        let mut game = Game::with_options(GameOptions { castling_enabled: false, ..Default::default() });
+        play(&mut game, (4, 1), (4, 3)); // e4
+        play(&mut game, (4, 6), (4, 4)); // e5
+        play(&mut game, (6, 0), (5, 2)); // Nf3
+        play(&mut game, (1, 7), (2, 5)); // Nc6
+        play(&mut game, (5, 0), (2, 3)); // Bc4
+        play(&mut game, (5, 7), (2, 4)); // Bc5
+
+        game.select_piece(4, 0).unwrap();
+        assert_eq!(game.get_moves().unwrap(), &[(5, 0), (4, 1)]);
+        assert!(game.to_fen().contains(" w - "));
+
+        let mut game = Game::from_fen(&game.to_fen().replacen(" - ", " KQkq ", 1)).unwrap();
+        game.select_piece(4, 0).unwrap();
+        assert!(game.get_moves().unwrap().contains(&(6, 0)));
+    }
+
+    #[test]
+    fn promotion_capture() {
+        let mut game = Game::from_fen("3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        play(&mut game, (4, 6), (3, 7)); // exd8
+        assert!(matches!(game.get_state(), State::SelectPromotion));
+        game.select_promotion(Piece::Queen).unwrap();
+
+        assert_eq!(game.get_black_positions(), &[(Piece::King, 7, 7)]);
+        assert!(game.get_white_positions().contains(&(Piece::Queen, 3, 7)));
+        assert!(matches!(game.get_current_player(), Player::Black));
+
+        let mv = game.last_move().unwrap();
+        assert_eq!(mv.kind, MoveKind::PromotionCapture);
+        assert_eq!(mv.promotion, Some(Piece::Queen));
+        assert_eq!(game.history()[..], [mv]);
+    }
+
+    #[test]
+    fn move_kind() {
+        let mut game = Game::from_fen("r3k3/8/8/3pP3/8/8/6P1/4K2R w Kq d6 0 1").unwrap();
+        play(&mut game, (4, 4), (3, 5)); // exd6
+        play(&mut game, (4, 7), (2, 7)); // O-O-O
+        play(&mut game, (6, 1), (6, 3)); // g4
+        play(&mut game, (3, 7), (3, 5)); // Rxd6
+        play(&mut game, (4, 0), (6, 0)); // O-O
+        play(&mut game, (2, 7), (1, 7)); // Kb8
+
+        use MoveKind::*;
+        let kinds = game.history().iter().map(|mv| mv.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [EnPassant, QueensideCastle, DoublePush, Capture, KingsideCastle, Quiet]);
+    }
+
+    #[test]
+    fn game_over() {
+        let mut game = Game::from_fen("7k/8/6K1/8/8/8/8/5Q2 w - - 0 1").unwrap();
+        play(&mut game, (5, 0), (5, 6)); // Qf7
+        assert!(matches!(game.get_state(), State::Stalemate));
+
+        let mut game = Game::from_fen("7k/6pp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        play(&mut game, (0, 0), (0, 7)); // Ra8#
+        assert!(matches!(game.get_state(), State::CheckMate));
+    }
+
+    #[test]
+    fn movable_pieces() {
+        let game = Game::new();
+        let mut movable = game.movable_pieces();
+        movable.sort();
+        let mut expected = (0..8).map(|x| (x, 1)).collect::<Vec<_>>();
+        expected.extend([(1, 0), (6, 0)]);
+        expected.sort();
+        assert_eq!(movable, expected);
+
+        let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(matches!(game.get_state(), State::Stalemate));
+        assert!(game.movable_pieces().is_empty());
+    }
+
+    #[test]
+    fn positions_oriented() {
+        let game = Game::new();
+
+        let white = game.get_positions_oriented(Player::White);
+        assert_eq!(white.len(), 32);
+        assert!(white.contains(&(Piece::King, Player::White, 4, 0)));
+        assert!(white.contains(&(Piece::King, Player::Black, 4, 7)));
+
+        // Black king on e8 appears on the bottom row, in the fourth column from the left
+        let black = game.get_positions_oriented(Player::Black);
+        assert_eq!(black.len(), 32);
+        assert!(black.contains(&(Piece::King, Player::Black, 3, 0)));
+        assert!(black.contains(&(Piece::King, Player::White, 3, 7)));
+        assert!(black.contains(&(Piece::Rook, Player::Black, 7, 0)));
+    }
+
+    #[test]
+    fn clock() {
+        let mut game = Game::with_clock(Clock::new(1000, 100));
+        game.tick(400);
+        play(&mut game, (4, 1), (4, 3)); // e4
+        assert_eq!(game.clock().unwrap().white_ms, 700);
+
+        game.tick(300);
+        assert_eq!(game.clock().unwrap().black_ms, 700);
+        play(&mut game, (4, 6), (4, 4)); // e5
+        assert_eq!(game.clock().unwrap().black_ms, 800);
+        assert_eq!(game.result(), None);
+
+        game.tick(1000);
+        assert!(matches!(game.get_state(), State::TimeOut));
+        assert_eq!(game.clock().unwrap().white_ms, 0);
+        assert_eq!(game.result(), Some(GameResult::Win(Player::Black)));
+        assert_eq!(game.select_piece(3, 1), Err(Error::InvalidState));
+
+        // Clock is stopped once the game is over
+        game.tick(1000);
+        assert_eq!(game.clock().unwrap().black_ms, 800);
+
+        // The result can't be taken back
+        assert_eq!(game.undo(), Err(Error::InvalidState));
+        assert_eq!(game.set_to_move(Player::Black), Err(Error::InvalidState));
+        assert!(matches!(game.get_state(), State::TimeOut));
+
+        game.reset();
+        assert_eq!(game.clock(), Some(Clock::new(1000, 100)));
+        assert!(matches!(game.get_state(), State::SelectPiece));
+    }
+
+    #[test]
+    fn algebraic() {
+        assert_eq!(Game::square_from_algebraic("a1"), Ok((0, 0)));
+        assert_eq!(Game::square_from_algebraic("e4"), Ok((4, 3)));
+        assert_eq!(Game::square_from_algebraic("h8"), Ok((7, 7)));
+        for s in ["", "e", "i1", "a9", "a0", "E4", "e44", "4e"] {
+            assert_eq!(Game::square_from_algebraic(s), Err(Error::InvalidPosition));
+        }
+
+        assert_eq!(Game::square_to_algebraic(4, 3).as_deref(), Ok("e4"));
+        assert_eq!(Game::square_to_algebraic(7, 7).as_deref(), Ok("h8"));
+        assert_eq!(Game::square_to_algebraic(8, 0), Err(Error::InvalidPosition));
+        assert_eq!(Game::square_to_algebraic(0, 8), Err(Error::InvalidPosition));
+    }
+
+    #[test]
+    fn draw_offer() {
+        let mut game = Game::new();
+        game.offer_draw(Player::White);
+        assert_eq!(game.draw_offer(), Some(Player::White));
+        game.respond_draw(true);
+        assert!(matches!(game.get_state(), State::Draw));
+        assert_eq!(game.result(), Some(GameResult::Draw));
+        assert_eq!(game.draw_offer(), None);
+
+        let mut game = Game::new();
+        game.offer_draw(Player::White);
+        game.respond_draw(false);
+        assert_eq!(game.draw_offer(), None);
+        assert_eq!(game.result(), None);
+
+        // Offer is withdrawn when offering player moves
+        game.offer_draw(Player::White);
+        play(&mut game, (4, 1), (4, 3)); // e4
+        assert_eq!(game.draw_offer(), None);
+        game.respond_draw(true);
+        assert_eq!(game.result(), None);
+
+        // but not when the opponent moves
+        game.offer_draw(Player::White);
+        play(&mut game, (4, 6), (4, 4)); // e5
+        assert_eq!(game.draw_offer(), Some(Player::White));
+    }
+
+    #[test]
+    fn state_display() {
+        use State::*;
+        let states = [SelectPiece, SelectMove, CheckMate, Stalemate, TimeOut, Draw, SelectPromotion];
+        let names = states.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        for (i, name) in names.iter().enumerate() {
+            assert!(!name.is_empty());
+            assert_eq!(name, states[i].as_str());
+            assert!(!names[..i].contains(name));
+        }
+        assert_eq!(CheckMate.to_string(), "checkmate");
+    }
+
+    #[test]
+    fn select_sq() {
+        let mut game = Game::new();
+        assert_eq!(game.select_piece_sq("e9"), Err(Error::InvalidPosition));
+        assert_eq!(game.select_piece_sq(""), Err(Error::InvalidPosition));
+
+        game.select_piece_sq("e2").unwrap();
+        assert_eq!(game.get_selected_pos(), Ok((4, 1)));
+        assert_eq!(game.select_move_sq("e44"), Err(Error::InvalidPosition));
+        game.select_move_sq("e4").unwrap();
+        assert_eq!(game.last_move().map(|mv| mv.to), Some((4, 3)));
+        assert_eq!(game.get_current_player(), Player::Black);
+    }
+
+    #[test]
+    fn pawn_can_promote() {
+        let mut game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(game.pawn_can_promote(1, 6));
+        assert!(!game.pawn_can_promote(4, 0));
+        assert!(!game.pawn_can_promote(9, 6));
+
+        play(&mut game, (1, 6), (1, 7));
+        assert!(matches!(game.get_state(), State::SelectPromotion));
+        assert!(!game.pawn_can_promote(1, 7));
+    }
+
+    #[test]
+    fn move_is_capture() {
+        let mut game = Game::new();
+        play(&mut game, (4, 1), (4, 3)); // e4
+        play(&mut game, (3, 6), (3, 4)); // d5
+        game.select_piece(4, 3).unwrap();
+        assert!(game.move_is_capture(3, 4));
+        assert!(!game.move_is_capture(4, 4));
+        assert!(!game.move_is_capture(8, 4));
+    }
+
+    #[test]
+    fn is_checkmate() {
+        let game = Game::from_fen("7k/6pp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert!(!game.is_checkmate());
+        assert!(!game.is_stalemate());
+
+        let game = Game::from_fen("R6k/6pp/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+        assert!(game.is_checkmate());
+        assert!(!game.is_stalemate());
+
+        let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!game.is_checkmate());
+        assert!(game.is_stalemate());
+    }
+
+    #[test]
+    fn from_epd() {
+        let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - bm e5; id \"open; 1.e4\";";
+        let (game, ops) = Game::from_epd(epd).unwrap();
+        assert!(game.position_eq_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -"));
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops["bm"], "e5");
+        assert_eq!(ops["id"], "open; 1.e4");
+
+        let (_, ops) = Game::from_epd("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+        assert!(ops.is_empty());
+
+        assert_eq!(Game::from_epd("4k3/8/8/8/8/8/8/4K3 w - - bm Kd2").err(), Some(Error::InvalidFen));
+        assert_eq!(Game::from_epd("4k3/8/8/8/8/8/8/4K3 w -").err(), Some(Error::InvalidFen));
+    }
+
+    #[test]
+    fn selected_moves_mask() {
+        let mut game = Game::new();
+        assert_eq!(game.selected_moves_mask(), Err(Error::InvalidState));
+        game.select_piece(6, 0).unwrap(); // Ng1
+        let mask = game.selected_moves_mask().unwrap();
+        assert_eq!(mask.count_ones() as usize, game.get_moves().unwrap().len());
+        assert_eq!(mask, 1 << (5 + 8 * 2) | 1 << (7 + 8 * 2));
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/3r4/3NK3 w - - 0 1").unwrap();
+        play(&mut game, (4, 0), (3, 1)); // Kxd2
+        assert!(matches!(game.get_state(), State::Draw));
+        assert_eq!(game.result(), Some(GameResult::Draw));
+        assert_eq!(game.draw_reason(), Some(DrawReason::InsufficientMaterial));
+    }
+
+    #[test]
+    fn draw_reason() {
+        let mut game = Game::new();
+        assert_eq!(game.draw_reason(), None);
+        play(&mut game, (4, 1), (4, 3)); // e4
+        game.offer_draw(Player::Black);
+        game.respond_draw(true);
+        assert_eq!(game.draw_reason(), Some(DrawReason::Agreement));
+        assert_eq!(game.undo(), Err(Error::InvalidState));
+        assert_eq!(game.goto_start(), Err(Error::InvalidState));
+        assert_eq!(game.draw_reason(), Some(DrawReason::Agreement));
+
+        let mut game = Game::from_fen("7k/8/4Q2K/8/8/8/8/8 w - - 0 1").unwrap();
+        play(&mut game, (4, 5), (5, 6)); // Qf7
+        assert!(matches!(game.get_state(), State::Stalemate));
+        assert_eq!(game.draw_reason(), Some(DrawReason::Stalemate));
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 99 80").unwrap();
+        assert_eq!(game.claim_draw(), Err(Error::NoDrawToClaim));
+        play(&mut game, (4, 0), (3, 0)); // Kd1
+        game.claim_draw().unwrap();
+        assert!(matches!(game.get_state(), State::Draw));
+        assert_eq!(game.result(), Some(GameResult::Draw));
+        assert_eq!(game.draw_reason(), Some(DrawReason::FiftyMove));
+        assert_eq!(game.claim_draw(), Err(Error::InvalidState));
+
+        // Knights out and back, twice
+        let mut game = Game::new();
+        for _ in 0..2 {
+            assert_eq!(game.claim_draw(), Err(Error::NoDrawToClaim));
+            play(&mut game, (6, 0), (5, 2));
+            play(&mut game, (6, 7), (5, 5));
+            play(&mut game, (5, 2), (6, 0));
+            play(&mut game, (5, 5), (6, 7));
+        }
+        game.claim_draw().unwrap();
+        assert_eq!(game.draw_reason(), Some(DrawReason::Threefold));
+
+        // Taking back a move undoes the draw
+        game.undo().unwrap();
+        assert!(matches!(game.get_state(), State::SelectPiece));
+        assert_eq!(game.draw_reason(), None);
+    }
+
+    #[test]
+    fn last_move_gave_check() {
+        let mut game = Game::new();
+        assert!(!game.last_move_gave_check());
+        play(&mut game, (4, 1), (4, 3)); // e4
+        assert!(!game.last_move_gave_check());
+        play(&mut game, (5, 6), (5, 5)); // f6
+        play(&mut game, (3, 0), (7, 4)); // Qh5+
+        assert!(game.last_move_gave_check());
+        play(&mut game, (6, 6), (6, 5)); // g6
+        assert!(!game.last_move_gave_check());
+
+        // Promotion delivering check
+        let mut game = Game::from_fen("7k/1P6/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        play(&mut game, (1, 6), (1, 7)); // b8
+        game.select_promotion(Piece::Queen).unwrap();
+        assert!(game.last_move_gave_check());
+    }
+
+    #[test]
+    fn promotion_in_check() {
+        // Only promotions block the check from the rook
+        let mut game = Game::from_fen("K6r/Pn2P3/8/8/4b3/8/8/7k w - - 0 1").unwrap();
+        assert_eq!(game.movable_pieces(), vec![(4, 6)]);
+        assert_eq!(game.moves_for_expanded(4, 6).len(), 4);
+
+        play(&mut game, (4, 6), (4, 7)); // e8
+        assert!(matches!(game.get_state(), State::SelectPromotion));
+        assert_eq!(game.get_current_player(), Player::White);
+
+        game.select_promotion(Piece::Rook).unwrap();
+        assert!(matches!(game.get_state(), State::SelectPiece));
+        assert_eq!(game.get_current_player(), Player::Black);
+        assert!(!game.board().is_player_in_check(Player::White));
+        assert!(!game.last_move_gave_check());
+
+        // Promoting with checkmate ends the game
+        let mut game = Game::from_fen("6k1/1P3ppp/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        play(&mut game, (1, 6), (1, 7)); // b8
+        game.select_promotion(Piece::Queen).unwrap();
+        assert!(game.is_checkmate());
+        assert_eq!(game.result(), Some(GameResult::Win(Player::White)));
+    }
+
+    #[test]
+    fn last_moved_piece_moves() {
+        let mut game = Game::new();
+        assert_eq!(game.last_moved_piece_moves(), vec![]);
+
+        play(&mut game, (4, 1), (4, 3)); // e4
+        assert_eq!(game.last_moved_piece_moves(), vec![(4, 4)]);
+
+        play(&mut game, (3, 6), (3, 4)); // d5
+        let mut moves = game.last_moved_piece_moves();
+        moves.sort();
+        assert_eq!(moves, vec![(3, 3), (4, 3)]);
+
+        play(&mut game, (6, 0), (5, 2)); // Nf3
+        let mut moves = game.last_moved_piece_moves();
+        moves.sort();
+        assert_eq!(moves, vec![(3, 3), (4, 4), (6, 0), (6, 4), (7, 3)]);
+    }
+
+    #[test]
+    fn promotions_played() {
+        let mut game = Game::from_fen("1n2k3/P7/8/8/8/8/7p/4K3 b - - 0 1").unwrap();
+        assert_eq!(game.promotions_played(), vec![]);
+
+        play(&mut game, (7, 1), (7, 0)); // h1
+        game.select_promotion(Piece::Knight).unwrap();
+        play(&mut game, (0, 6), (1, 7)); // axb8
+        game.select_promotion(Piece::Queen).unwrap();
+        assert_eq!(game.promotions_played(), vec![(Player::Black, Piece::Knight), (Player::White, Piece::Queen)]);
+    }
+
+    #[test]
+    fn last_move_was_castling() {
+        let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(game.last_move_was_castling(), None);
+        play(&mut game, (4, 0), (6, 0)); // O-O
+        assert_eq!(game.last_move_was_castling(), Some(true));
+        play(&mut game, (4, 7), (2, 7)); // O-O-O
+        assert_eq!(game.last_move_was_castling(), Some(false));
+        play(&mut game, (0, 0), (0, 5)); // Ra6
+        assert_eq!(game.last_move_was_castling(), None);
+    }
+
+    #[test]
+    fn piece_id_at() {
+        let mut game = Game::new();
+        let knight = game.piece_id_at(6, 0).unwrap();
+        let pawn = game.piece_id_at(4, 6).unwrap();
+        assert_eq!(game.piece_id_at(4, 4), None);
+        assert_ne!(knight, pawn);
+
+        play(&mut game, (6, 0), (5, 2)); // Nf3
+        play(&mut game, (4, 6), (4, 4)); // e5
+        assert_eq!(game.piece_id_at(5, 2), Some(knight));
+        assert_eq!(game.piece_id_at(4, 4), Some(pawn));
+        assert_eq!(game.piece_id_at(6, 0), None);
+
+        play(&mut game, (5, 2), (4, 4)); // Nxe5
+        assert_eq!(game.piece_id_at(4, 4), Some(knight));
+    }
+
+    #[test]
+    fn play_san_line() {
+        let mut game = Game::new();
+        game.play_san_line("1. e4 e5 2. Nf3 Nc6").unwrap();
+        assert_eq!(game.to_fen(), "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+
+        game.play_san_line("3.Bb5 a6 4.Bxc6 dxc6 5.0-0").unwrap();
+        assert_eq!(game.san_history(), ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O"]);
+
+        let mut game = Game::new();
+        assert_eq!(game.play_san_line("1. f3 e5 2. g4 Qh4# 0-1"), Ok(()));
+        assert!(game.is_checkmate());
+
+        // Stops at the first illegal move
+        let mut game = Game::new();
+        assert_eq!(game.play_san_line("1. e4 e5 2. Ke3 Nc6"), Err(Error::InvalidSan(4)));
+        assert_eq!(game.ply(), 2);
+        assert_eq!(game.play_san_line("Nf3 Nf3"), Err(Error::InvalidSan(1)));
+        assert_eq!(game.play_san_line("2... d5 3. c4 Qd9 4. Nc3"), Err(Error::InvalidSan(4)));
+        assert_eq!(game.ply(), 5);
+    }
+
+    #[test]
+    fn san_iter() {
+        let mut game = Game::new();
+        assert_eq!(game.san_iter().next(), None);
+
+        play(&mut game, (5, 1), (5, 2)); // f3
+        play(&mut game, (4, 6), (4, 4)); // e5
+        play(&mut game, (6, 1), (6, 3)); // g4
+        play(&mut game, (3, 7), (7, 3)); // Qh4#
+
+        let san = game.san_iter().collect::<Vec<_>>();
+        assert_eq!(san, ["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(san, game.san_history());
+
+        // Promotion and castling
+        let mut game = Game::from_fen("r3k3/1P6/8/8/8/8/8/4K2R w Kq - 0 1").unwrap();
+        play(&mut game, (1, 6), (0, 7)); // bxa8
+        game.select_promotion(Piece::Knight).unwrap();
+        play(&mut game, (4, 7), (3, 7)); // Kd8
+        play(&mut game, (4, 0), (6, 0)); // O-O
+        assert_eq!(game.san_history(), ["bxa8=N", "Kd8", "O-O"]);
+    }
+
+    #[test]
+    fn moves_for_expanded() {
+        let game = Game::from_fen("4k3/1P6/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+
+        let moves = game.moves_for_expanded(1, 6);
+        assert_eq!(moves.len(), 4);
+        for (mv, piece) in moves.iter().zip([Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight]) {
+            assert_eq!((mv.from, mv.to), ((1, 6), (1, 7)));
+            assert_eq!(mv.promotion, Some(piece));
+            assert_eq!(mv.kind, MoveKind::Promotion);
+        }
+
+        let moves = game.moves_for_expanded(0, 0);
+        assert_eq!(moves.len(), 2);
+        assert!(moves.iter().all(|mv| mv.promotion.is_none()));
+
+        assert!(game.moves_for_expanded(4, 7).is_empty());
+        assert!(game.moves_for_expanded(3, 3).is_empty());
+        assert!(game.moves_for_expanded(8, 0).is_empty());
+    }
+
+    #[test]
+    fn repetition_count() {
+        let mut game = Game::new();
+        assert_eq!(game.repetition_count(), 1);
+
+        let knights = [((6, 0), (5, 2)), ((6, 7), (5, 5)), ((5, 2), (6, 0)), ((5, 5), (6, 7))];
+        for (from, to) in knights {
+            play(&mut game, from, to);
+        }
+        assert_eq!(game.repetition_count(), 2);
+        for (from, to) in knights {
+            play(&mut game, from, to);
+        }
+        assert_eq!(game.repetition_count(), 3);
+
+        game.undo().unwrap();
+        assert_eq!(game.repetition_count(), 2);
+        game.redo().unwrap();
+        assert_eq!(game.repetition_count(), 3);
+        game.goto_ply(4).unwrap();
+        assert_eq!(game.repetition_count(), 2);
+        game.goto_start().unwrap();
+        assert_eq!(game.repetition_count(), 1);
+
+        // Position with a pawn on the last rank is not counted
+        let mut game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        play(&mut game, (1, 6), (1, 7));
+        game.select_promotion(Piece::Queen).unwrap();
+        assert_eq!(game.hashes.len(), 2);
+        assert_eq!(game.repetition_count(), 1);
+    }
+
+    #[test]
+    fn pass_turn() {
+        let mut game = Game::new();
+        play(&mut game, (4, 1), (4, 3)); // e4
+        game.pass_turn().unwrap();
+        assert_eq!(game.get_current_player(), Player::White);
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
+
+        // History is discarded
+        assert_eq!(game.ply(), 0);
+        assert_eq!(game.undo(), Err(Error::NothingToUndo));
+        game.pass_turn().unwrap();
+        assert_eq!(game.get_current_player(), Player::Black);
+
+        // Opponent is stalemated
+        let mut game = Game::from_fen("k7/8/1Q6/8/8/8/8/4K3 w - - 0 1").unwrap();
+        game.pass_turn().unwrap();
+        assert!(game.is_stalemate());
+        assert_eq!(game.pass_turn(), Err(Error::InvalidState));
+
+        // Not while in check
+        let mut game = Game::from_fen("k7/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        assert_eq!(game.pass_turn(), Err(Error::InvalidState));
+        assert_eq!(game.get_current_player(), Player::White);
+    }
+
+    #[test]
+    fn set_to_move() {
+        let mut game = Game::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert!(matches!(game.get_state(), State::CheckMate));
+
+        // Black would be left in check with White to move
+        assert_eq!(game.set_to_move(Player::White), Err(Error::IllegalPosition));
+        assert!(matches!(game.get_state(), State::CheckMate));
+        assert_eq!(game.get_current_player(), Player::Black);
+        assert_eq!(game.result(), Some(GameResult::Win(Player::White)));
+
+        let mut game = Game::new();
+        play(&mut game, (4, 1), (4, 3)); // e4
+        game.set_to_move(Player::White).unwrap();
+        assert_eq!(game.ply(), 0);
+        assert_eq!(game.get_current_player(), Player::White);
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(game.undo(), Err(Error::NothingToUndo));
+
+        // Not while a promotion is pending
+        let mut game = Game::new();
+        play_to_promotion(&mut game);
+        assert_eq!(game.set_to_move(Player::Black), Err(Error::InvalidState));
+        assert!(matches!(game.get_state(), State::SelectPromotion));
+        game.select_promotion(Piece::Queen).unwrap();
+        assert!(game.get_white_positions().contains(&(Piece::Queen, 0, 7)));
+    }
+
+    #[test]
+    fn default() {
+        let game = Game::default();
+        assert_eq!(game.to_fen(), Game::new().to_fen());
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!(matches!(game.get_state(), State::SelectPiece));
+    }
+
+    #[test]
+    fn try_select_piece() {
+        let mut game = Game::new();
+        assert_eq!(game.try_select_piece(4, 3), Ok(SelectOutcome::EmptySquare));
+        assert!(matches!(game.get_state(), State::SelectPiece));
+        assert_eq!(game.try_select_piece(4, 6), Ok(SelectOutcome::OpponentPiece));
+        assert!(matches!(game.get_state(), State::SelectPiece));
+        assert_eq!(game.try_select_piece(8, 0), Err(Error::InvalidPosition));
+
+        assert_eq!(game.try_select_piece(0, 0), Ok(SelectOutcome::NoLegalMoves));
+        assert!(matches!(game.get_state(), State::SelectMove));
+        assert_eq!(game.get_moves(), Ok(&[][..]));
+        assert_eq!(game.try_select_piece(4, 1), Err(Error::InvalidState));
+
+        let mut game = Game::new();
+        assert_eq!(game.try_select_piece(4, 1), Ok(SelectOutcome::Selected));
+        assert_eq!(game.get_selected_pos(), Ok((4, 1)));
+    }
+
+    #[test]
+    fn apply_move() {
+        let mut game = Game::new();
+        play_to_promotion(&mut game);
+        game.select_promotion(Piece::Knight).unwrap();
+        play(&mut game, (6, 7), (5, 5)); // Nf6
+
+        let mut applied = Game::new();
+        for &mv in game.history() {
+            applied.apply_move(mv).unwrap();
+        }
+        assert_eq!(applied.to_fen(), game.to_fen());
+        assert_eq!(applied.history(), game.history());
+
+        // Promotes to queen by default
+        let mut game = Game::new();
+        play_to_promotion(&mut game);
+        let mv = game.last_move().unwrap();
+        let mut applied = Game::new();
+        for &mv in &game.history()[..game.ply() as usize - 1] {
+            applied.apply_move(mv).unwrap();
+        }
+        applied.apply_move(Move { promotion: None, ..mv }).unwrap();
+        assert_eq!(applied.last_move().unwrap().promotion, Some(Piece::Queen));
+        assert!(applied.get_white_positions().contains(&(Piece::Queen, 0, 7)));
+
+        let mut game = Game::new();
+        assert_eq!(game.apply_move(Move::new((4, 1), (4, 4), None)), Err(Error::InvalidMove(0)));
+        assert_eq!(game.apply_move(Move::new((4, 6), (4, 4), None)), Err(Error::InvalidMove(0)));
+        assert_eq!(game.apply_move(Move::new((9, 1), (4, 4), None)), Err(Error::InvalidMove(0)));
+        assert_eq!(game.ply(), 0);
+
+        // The clock increment is added as with select_move
+        let mut selected = Game::with_clock(Clock::new(1000, 500));
+        play(&mut selected, (4, 1), (4, 3)); // e4
+        let mut applied = Game::with_clock(Clock::new(1000, 500));
+        applied.apply_move(Move::new((4, 1), (4, 3), None)).unwrap();
+        assert_eq!(applied.clock().unwrap().white_ms, 1500);
+        assert_eq!(applied.clock(), selected.clock());
+
+        // Illegal moves don't touch the clock
+        assert!(applied.apply_move(Move::new((4, 1), (4, 3), None)).is_err());
+        assert_eq!(applied.clock(), selected.clock());
+    }
+
+    #[test]
+    fn replay() {
+        let mut game = Game::new();
+        play_to_promotion(&mut game);
+        game.select_promotion(Piece::Queen).unwrap();
+
+        let replayed = Game::replay(game.history()).unwrap();
+        assert_eq!(replayed.to_fen(), game.to_fen());
+        assert_eq!(replayed.history(), game.history());
+    }
+
+    #[test]
+    fn moves_string() {
+        let mut game = Game::new();
+        assert_eq!(game.moves_to_string(), "");
+        assert_eq!(Game::from_moves_string("").unwrap().to_fen(), game.to_fen());
+
+        game.play_san_line("1. e4 e5 2. Nf3 Nc6 3. Bc4 Nf6 4. O-O").unwrap();
+        assert_eq!(game.moves_to_string(), "e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1");
+        let loaded = Game::from_moves_string(&game.moves_to_string()).unwrap();
+        assert_eq!(loaded.to_fen(), game.to_fen());
+
+        let mut game = Game::new();
+        play_to_promotion(&mut game);
+        game.select_promotion(Piece::Knight).unwrap();
+        assert!(game.moves_to_string().ends_with('n'));
+        let loaded = Game::from_moves_string(&game.moves_to_string()).unwrap();
+        assert_eq!(loaded.to_fen(), game.to_fen());
+
+        assert_eq!(Game::from_moves_string("e2e4 e7e5x").err(), Some(Error::InvalidMove(1)));
+        assert_eq!(Game::from_moves_string("e2e4 e2e4").err(), Some(Error::InvalidMove(1)));
+        assert_eq!(Game::from_moves_string("i2e4").err(), Some(Error::InvalidMove(0)));
+    }
+
+    #[test]
+    fn replay_invalid() {
+        let moves = [
+            Move::new((4, 1), (4, 3), None),
+            Move::new((4, 6), (4, 3), None),
+        ];
+        assert_eq!(Game::replay(&moves).err(), Some(Error::InvalidMove(1)));
+    }
+
+    #[test]
+    fn start_fen() {
+        let mut game = Game::new();
+        assert_eq!(game.start_fen(), None);
+        game.play_san_line("e4 e5").unwrap();
+        assert_eq!(game.start_fen(), None);
+        assert!(!game.to_pgn().contains("FEN"));
+        assert!(game.to_pgn().ends_with("\n\n1. e4 e5 *"));
+
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12";
+        let mut game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.start_fen(), Some(fen));
+        game.play_san_line("Kd7 e4 Kc6").unwrap();
+        assert_eq!(game.start_fen(), Some(fen));
+
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n"));
+        assert!(pgn.ends_with("\n\n12... Kd7 13. e4 Kc6 *"));
+
+        game.reset();
+        assert_eq!(game.start_fen(), None);
+
+        // The standard initial position needs no FEN
+        let game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(game.start_fen(), None);
+        assert!(!game.to_pgn().contains("FEN"));
+    }
+
+    #[test]
+    fn pgn_termination() {
+        let mut game = Game::from_fen("k7/8/1Q6/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(game.to_pgn().contains("[Result \"1/2-1/2\"]\n[Termination \"normal\"]\n"));
+        assert!(game.to_pgn().ends_with("\n\n{Drawn by stalemate} 1/2-1/2"));
+
+        game = Game::new();
+        assert!(!game.to_pgn().contains("Termination"));
+        game.play_san_line("e4").unwrap();
+        game.offer_draw(Player::White);
+        game.respond_draw(true);
+        assert!(game.to_pgn().contains("[Termination \"normal\"]\n"));
+        assert!(game.to_pgn().ends_with("1. e4 {Drawn by agreement} 1/2-1/2"));
+
+        game = Game::with_clock(Clock::new(1000, 0));
+        game.play_san_line("e4").unwrap();
+        game.tick(1000);
+        assert!(game.to_pgn().contains("[Result \"1-0\"]\n[Termination \"time forfeit\"]\n"));
+        assert!(game.to_pgn().ends_with("1. e4 1-0"));
+    }
+
+    #[test]
+    fn legal_move_map() {
+        let mut game = Game::new();
+        let map = game.legal_move_map();
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.values().map(Vec::len).sum::<usize>(), 20);
+        assert_eq!(map[&(6, 0)].len(), 2);
+        assert!(!map.contains_key(&(4, 0)));
+
+        game.select_piece(4, 1).unwrap();
+        assert_eq!(game.legal_move_map(), map);
+
+        let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(game.legal_move_map().is_empty());
+    }
+
+    #[test]
+    fn terminal_state() {
+        let fens = [
+            "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1",
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            "4k3/8/8/8/8/8/8/4K1N1 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+        ];
+        for fen in fens {
+            let game = Game::from_fen(fen).unwrap();
+            assert_eq!(game.result(), Board::from_fen(fen).unwrap().terminal_state(), "{}", fen);
+        }
+    }
 }
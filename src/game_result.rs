@@ -0,0 +1,11 @@
+
+use crate::player::Player;
+
+/// Outcome of a finished game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    /// The player won.
+    Win(Player),
+    /// The game was drawn.
+    Draw,
+}
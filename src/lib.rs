@@ -6,10 +6,20 @@
 //! * [Game::get_state]: get the current [State] of the game.
 //! * [Game::get_current_player]: get the current [Player].
 //! * [Game::get_black_positions]/[Game::get_white_positions]: get pieces and corresponding
-//! positions.
+//!   positions.
 //! * [Game::get_moves]: get all destination positions corresponding to legal moves for piece
-//! previously selected with [Game::select_piece].
+//!   previously selected with [Game::select_piece].
 //! * [Game::get_selected_pos]: get position of piece selected with [Game::select_piece].
+//! * [Game::history]: get all [Move]s played so far.
+//! * [Game::san_history]: get all moves played so far in Standard Algebraic Notation.
+//! * [Game::to_fen]: get the current position in Forsyth-Edwards Notation.
+//! * [Game::to_pgn]: get the game so far in Portable Game Notation.
+//! * [Game::board]: get the underlying [Board], for lower level move generation.
+//! * [Game::result]: get the [GameResult] once the game is over.
+//! * [Game::draw_reason]: get the [DrawReason] if the game is drawn.
+//!
+//! With the `wasm` feature enabled, the `wasm` module provides flat versions of these
+//! methods, better suited for crossing the WebAssembly boundary.
 //!
 //! Some methods are associated with a certain state, and returns [Error::InvalidState] if called when game is
 //! in a different state. These methods are:
@@ -65,6 +75,7 @@
 //! #    use ludviggl_chess::Piece;
 //! #    pub fn get_clicked_square() -> (u8, u8) { (0, 0,) }
 //! #    pub fn game_over() {}
+//! #    pub fn draw() {}
 //! #    pub fn get_promotion() -> Piece { Piece::Queen }
 //! # }
 //! # let mut game = Game::new();
@@ -80,9 +91,12 @@
 //!         game.select_move(x, y).unwrap(); // we know state is State::SelectMove
 //!                                          // and position is valid, hence .unwrap()
 //!     },
-//!     State::CheckMate => {
+//!     State::CheckMate | State::TimeOut => {
 //!         frontend::game_over();
 //!     },
+//!     State::Stalemate | State::Draw => {
+//!         frontend::draw();
+//!     },
 //!     State::SelectPromotion => {
 //!         let piece = frontend::get_promotion();
 //!         game.select_promotion(piece).unwrap(); // we know state is State::SelectPromotion
@@ -97,13 +111,24 @@ extern crate lazy_static;
 pub mod piece;
 pub mod player;
 pub mod game;
+pub mod chess_move;
+pub mod clock;
+pub mod game_result;
+pub mod engine;
 mod board;
 #[allow(dead_code)]
-mod utils;
+pub mod utils;
 mod moves;
 pub mod error;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub use piece::Piece;
 pub use player::Player;
-pub use game::{ Game, State, };
+pub use game::{ DrawReason, Game, GameOptions, SelectOutcome, State, };
+pub use game_result::GameResult;
+pub use clock::Clock;
+pub use chess_move::{ Move, MoveKind, };
+pub use board::{ Board, CastlingRights, CastlingStatus, Outcome, PawnStructure, Snapshot, };
+pub use utils::BitIterator;
 pub use error::Error;
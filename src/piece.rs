@@ -1,11 +1,44 @@
 
 /// Represent the different kinds of pieces.
-#[derive(Clone, Copy, Debug)]
+/// Ordered by rank: pawn, knight, bishop, rook, queen, king.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Piece {
     Pawn,
-    Rook,
     Knight,
     Bishop,
+    Rook,
     Queen,
     King,
 }
+
+impl Piece {
+
+    /// Returns material value of piece in centipawns.
+    /// The king has no material value.
+    pub fn value(self) -> i32 {
+        match self {
+            Piece::Pawn   => 100,
+            Piece::Knight => 320,
+            Piece::Bishop => 330,
+            Piece::Rook   => 500,
+            Piece::Queen  => 900,
+            Piece::King   => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::piece::*;
+
+    #[test]
+    fn ordering() {
+        use Piece::*;
+        let mut pieces = [King, Rook, Pawn, Queen, Bishop, Knight];
+        pieces.sort();
+        assert_eq!(pieces, [Pawn, Knight, Bishop, Rook, Queen, King]);
+        assert!(Knight < Bishop);
+        assert!(Queen < King);
+    }
+}
@@ -1,8 +1,15 @@
 
 /// Represents the current player.
-#[derive(Clone, Copy, Debug)]
-pub enum Player { White, Black, }
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Player { #[default] White, Black, }
 
-impl Default for Player {
-    fn default() -> Self { Player::White }
+impl Player {
+
+    /// Returns the other player.
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::White => Player::Black,
+            Player::Black => Player::White,
+        }
+    }
 }
@@ -1,63 +1,73 @@
 
+use crate::player::Player;
+
 const FILL: u64 = 0xffffffffffffffff;
 
-pub fn flatten(x: u8, y: u8) -> usize {
+pub(crate) fn flatten(x: u8, y: u8) -> usize {
     (x | (y << 3)) as usize
 }
 
-pub fn bit(b: u64) -> u64 {
+pub(crate) fn bit(b: u64) -> u64 {
     1 << b
 }
 
-pub fn shr_unchecked(x: u64, s: u64) -> u64 {
+// Shifts x right by s, or returns 0 if s >= 64
+pub(crate) fn shr_unchecked(x: u64, s: u64) -> u64 {
     x.checked_shr(s.try_into().unwrap()).unwrap_or(0)
 }
 
-pub fn shl_unchecked(x: u64, s: u64) -> u64 {
+// Shifts x left by s, or returns 0 if s >= 64
+pub(crate) fn shl_unchecked(x: u64, s: u64) -> u64 {
     x.checked_shl(s.try_into().unwrap()).unwrap_or(0)
 }
 
-pub fn flatten_bit(x: u8, y: u8) -> u64 {
+pub(crate) fn flatten_bit(x: u8, y: u8) -> u64 {
     bit(flatten(x, y) as u64)
 }
 
-pub fn unflatten(i: usize) -> (u8, u8) {
+pub(crate) fn unflatten(i: usize) -> (u8, u8) {
     ((i & 7) as u8, (i >> 3) as u8)
 }
 
-pub fn unflatten_bit(m: u64) -> (u8, u8) {
+pub(crate) fn unflatten_bit(m: u64) -> (u8, u8) {
     unflatten(m.trailing_zeros() as usize)
 }
 
-// Fills bits left of ms 1 of m, incl ms 1
-pub fn fill_left_incl(m: u64) -> u64 {
+// Fills bits left of ls 1 of m, incl ls 1.
+// All fill functions return 0 if m is 0.
+pub(crate) fn fill_left_incl(m: u64) -> u64 {
+    if m == 0 { return 0; }
     shl_unchecked(FILL, m.trailing_zeros().into())
 }
 
-pub fn fill_left_excl(m: u64) -> u64 {
+pub(crate) fn fill_left_excl(m: u64) -> u64 {
+    if m == 0 { return 0; }
     shl_unchecked(FILL, (m.trailing_zeros() + 1).into())
 }
 
-pub fn fill_right_incl(m: u64) -> u64 {
+// Fills bits right of ms 1 of m, incl ms 1
+pub(crate) fn fill_right_incl(m: u64) -> u64 {
+    if m == 0 { return 0; }
     shr_unchecked(FILL, m.leading_zeros().into())
 }
 
-pub fn fill_right_excl(m: u64) -> u64 {
+pub(crate) fn fill_right_excl(m: u64) -> u64 {
+    if m == 0 { return 0; }
     shr_unchecked(FILL, (m.leading_zeros() + 1).into())
 }
 
 // fill between bits b1 & b2, including b1 & b2
-pub fn fill_between_incl(b1: u64, b2: u64) -> u64 {
+pub(crate) fn fill_between_incl(b1: u64, b2: u64) -> u64 {
     (fill_left_incl(b1) & fill_right_incl(b2)) |
     (fill_left_incl(b2) & fill_right_incl(b1))
 }
 
-pub fn fill_between_excl(b1: u64, b2: u64) -> u64 {
+pub(crate) fn fill_between_excl(b1: u64, b2: u64) -> u64 {
     (fill_left_excl(b1) & fill_right_excl(b2)) |
     (fill_left_excl(b2) & fill_right_excl(b1))
 }
 
-pub fn neg_diag_through(b: u64) -> u64 {
+pub(crate) fn neg_diag_through(b: u64) -> u64 {
 
     debug_assert!(b > 0);
 
@@ -71,7 +81,7 @@ pub fn neg_diag_through(b: u64) -> u64 {
     }
 }
 
-pub fn pos_diag_through(b: u64) -> u64 {
+pub(crate) fn pos_diag_through(b: u64) -> u64 {
 
     debug_assert!(b > 0);
 
@@ -88,7 +98,7 @@ pub fn pos_diag_through(b: u64) -> u64 {
 
 // gets ray between bits, icluding endpoints
 // returns 0 if not on same diagonal
-pub fn diag_ray_between_incl(b1: u64, b2: u64) -> u64 {
+pub(crate) fn diag_ray_between_incl(b1: u64, b2: u64) -> u64 {
 
     let dn = neg_diag_through(b1);
     let dp = pos_diag_through(b1);
@@ -97,7 +107,7 @@ pub fn diag_ray_between_incl(b1: u64, b2: u64) -> u64 {
     d & fill_between_incl(b1, b2)
 }
 
-pub fn ortho_ray_between_incl(b1: u64, b2: u64) -> u64 {
+pub(crate) fn ortho_ray_between_incl(b1: u64, b2: u64) -> u64 {
     
     let h = byte_mask(b1.trailing_zeros().try_into().unwrap());
     let v = col_mask(b1.trailing_zeros().try_into().unwrap());
@@ -106,7 +116,7 @@ pub fn ortho_ray_between_incl(b1: u64, b2: u64) -> u64 {
     o & fill_between_incl(b1, b2)
 }
 
-pub fn diag_ray_between_excl(b1: u64, b2: u64) -> u64 {
+pub(crate) fn diag_ray_between_excl(b1: u64, b2: u64) -> u64 {
 
     let dn = neg_diag_through(b1);
     let dp = pos_diag_through(b1);
@@ -115,7 +125,7 @@ pub fn diag_ray_between_excl(b1: u64, b2: u64) -> u64 {
     d & fill_between_excl(b1, b2)
 }
 
-pub fn ortho_ray_between_excl(b1: u64, b2: u64) -> u64 {
+pub(crate) fn ortho_ray_between_excl(b1: u64, b2: u64) -> u64 {
     
     let h = byte_mask(b1.trailing_zeros().try_into().unwrap());
     let v = col_mask(b1.trailing_zeros().try_into().unwrap());
@@ -124,33 +134,81 @@ pub fn ortho_ray_between_excl(b1: u64, b2: u64) -> u64 {
     o & fill_between_excl(b1, b2)
 }
 
+/// Returns the file (x coordinate) of flat index `sq`.
+pub fn file_of(sq: usize) -> u8 {
+    (sq & 7) as u8
+}
+
+/// Returns the rank (y coordinate) of flat index `sq`.
+pub fn rank_of(sq: usize) -> u8 {
+    (sq >> 3) as u8
+}
+
+/// Returns the number of king moves between flat indices `a` and `b`.
+pub fn chebyshev_distance(a: usize, b: usize) -> u8 {
+    file_of(a).abs_diff(file_of(b)).max(rank_of(a).abs_diff(rank_of(b)))
+}
+
+/// Returns the sum of file and rank distances between flat indices `a` and `b`.
+pub fn manhattan_distance(a: usize, b: usize) -> u8 {
+    file_of(a).abs_diff(file_of(b)) + rank_of(a).abs_diff(rank_of(b))
+}
+
+/// Returns the rank (y coordinate) on which pawns of `player` promote.
+pub fn promotion_rank(player: Player) -> u8 {
+    match player {
+        Player::White => 7,
+        Player::Black => 0,
+    }
+}
+
 // Fills byte containg bit number i
-pub fn byte_mask(i: usize) -> u64 {
+pub(crate) fn byte_mask(i: usize) -> u64 {
     0xff << (i & 0b111000)
 }
 
-pub fn col_mask(i: usize) -> u64 {
+pub(crate) fn col_mask(i: usize) -> u64 {
     0x0101010101010101 << (i & 0b111)
 }
 
-pub fn _print_bitboard(b: u64) {
+// Algebraic name of position, e.g. "e4"
+pub(crate) fn square_name(x: u8, y: u8) -> String {
+    format!("{}{}", (b'a' + x) as char, y + 1)
+}
+
+// Parses algebraic name of position
+pub(crate) fn parse_square(s: &str) -> Option<(u8, u8)> {
+    match s.as_bytes() {
+        &[f, r] => {
+            let x = f.wrapping_sub(b'a');
+            let y = r.wrapping_sub(b'1');
+            if x < 8 && y < 8 { Some((x, y)) } else { None }
+        },
+        _ => None,
+    }
+}
+
+pub(crate) fn _print_bitboard(b: u64) {
     for i in (0..64).rev() {
         let b = (b >> i) & 1;
         let s = if b == 0 { '.' } else { 'x' };
         print!("{} ", s);
         if i % 8 == 0 {
-            println!("");
+            println!();
         }
     }
-    println!("");
+    println!();
 }
 
+/// Iterates over the set bits of a mask, yielding each as a mask
+/// with a single bit set, from least significant to most significant.
 pub struct BitIterator {
     value: u64,
     offset: u32,
 }
 
 impl BitIterator {
+    /// Creates an iterator over the set bits of `value`.
     pub fn new(value: u64) -> Self { Self { value, offset: 0 } }
 }
 
@@ -162,7 +220,7 @@ impl Iterator for BitIterator {
         if self.value == 0 { None } else {
             self.offset = self.value.trailing_zeros();
             let bit = 1 << self.offset;
-            self.value = self.value & !bit;
+            self.value &= !bit;
             Some(bit)
         }        
     }
@@ -183,6 +241,23 @@ mod test {
         assert_eq!(it.next(), None);
     }
     
+    #[test]
+    fn distance() {
+        let (a1, e4, h8) = (0, 28, 63);
+        assert_eq!((file_of(e4), rank_of(e4)), (4, 3));
+        assert_eq!(chebyshev_distance(a1, h8), 7);
+        assert_eq!(manhattan_distance(a1, h8), 14);
+        assert_eq!(chebyshev_distance(e4, a1), 4);
+        assert_eq!(manhattan_distance(e4, a1), 7);
+        assert_eq!(chebyshev_distance(e4, e4), 0);
+    }
+
+    #[test]
+    fn promotion_rank() {
+        assert_eq!(super::promotion_rank(Player::White), 7);
+        assert_eq!(super::promotion_rank(Player::Black), 0);
+    }
+
     #[test]
     fn fill() {
         let x = 0x00_00_00_00_0a_00_00_00;
@@ -204,6 +279,24 @@ mod test {
         assert_eq!(v, e);
     }
 
+    #[test]
+    fn fill_zero() {
+        assert_eq!(fill_left_incl(0), 0);
+        assert_eq!(fill_left_excl(0), 0);
+        assert_eq!(fill_right_incl(0), 0);
+        assert_eq!(fill_right_excl(0), 0);
+        assert_eq!(fill_between_incl(0, 0), 0);
+        assert_eq!(fill_between_excl(0, 0), 0);
+
+        // Edge bits
+        assert_eq!(fill_left_excl(1 << 63), 0);
+        assert_eq!(fill_right_excl(1), 0);
+        assert_eq!(fill_left_incl(1), FILL);
+        assert_eq!(fill_right_incl(1 << 63), FILL);
+        assert_eq!(shl_unchecked(FILL, 64), 0);
+        assert_eq!(shr_unchecked(FILL, 65), 0);
+    }
+
     #[test]
     fn flatten() {
         let x = 2;
@@ -0,0 +1,169 @@
+//! Flat API for targets such as WebAssembly, where slices of tuples and
+//! borrowed results don't cross the boundary well. Enabled with the `wasm`
+//! feature. All functions return owned data and plain integers.
+//!
+//! Pieces are encoded as `0` pawn, `1` knight, `2` bishop, `3` rook,
+//! `4` queen and `5` king. Players are encoded as `0` white and `1` black.
+//! [Game::to_fen] can be used as is.
+
+use crate::{
+    game::{ Game, State, },
+    piece::Piece,
+    player::Player,
+    utils,
+};
+
+impl Game {
+
+    /// Returns legal moves for the piece of the current player at position,
+    /// packed as consecutive x, y pairs. Empty if there is no such piece,
+    /// or if game state is not [State::SelectPiece] or [State::SelectMove].
+    pub fn legal_moves_flat(&self, x: u8, y: u8) -> Vec<u8> {
+
+        if !matches!(self.get_state(), State::SelectPiece | State::SelectMove)
+            || x >= 8 || y >= 8
+        {
+            return Vec::new();
+        }
+
+        let board = self.board();
+        match board.id_from_pos(x, y) {
+            None => Vec::new(),
+            Some(id) => utils::BitIterator::new(board.get_legal_moves(id))
+                .flat_map(|m| {
+                    let (x, y) = utils::unflatten_bit(m);
+                    [x, y]
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns pieces of white player packed as consecutive piece, x, y triples.
+    pub fn white_positions_flat(&self) -> Vec<u8> {
+        flatten_positions(self.get_white_positions())
+    }
+
+    /// Returns pieces of black player packed as consecutive piece, x, y triples.
+    pub fn black_positions_flat(&self) -> Vec<u8> {
+        flatten_positions(self.get_black_positions())
+    }
+
+    /// Returns the current player, `0` for white and `1` for black.
+    pub fn current_player_flat(&self) -> u8 {
+        match self.get_current_player() {
+            Player::White => 0,
+            Player::Black => 1,
+        }
+    }
+
+    /// Returns the state of the game, `0` for [State::SelectPiece], `1` for
+    /// [State::SelectMove], `2` for [State::CheckMate], `3` for
+    /// [State::SelectPromotion], `4` for [State::Stalemate], `5` for
+    /// [State::TimeOut] and `6` for [State::Draw].
+    pub fn state_flat(&self) -> u8 {
+        match self.get_state() {
+            State::SelectPiece     => 0,
+            State::SelectMove      => 1,
+            State::CheckMate       => 2,
+            State::SelectPromotion => 3,
+            State::Stalemate       => 4,
+            State::TimeOut         => 5,
+            State::Draw            => 6,
+        }
+    }
+
+    /// Same as [Game::select_piece], returning whether it succeeded.
+    pub fn select_piece_flat(&mut self, x: u8, y: u8) -> bool {
+        self.select_piece(x, y).is_ok()
+    }
+
+    /// Same as [Game::select_move], returning whether it succeeded.
+    pub fn select_move_flat(&mut self, x: u8, y: u8) -> bool {
+        self.select_move(x, y).is_ok()
+    }
+
+    /// Same as [Game::select_promotion] with encoded piece, returning whether it succeeded.
+    pub fn select_promotion_flat(&mut self, piece: u8) -> bool {
+        match code_piece(piece) {
+            Some(piece) => self.select_promotion(piece).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Replaces the game with a game from a position in Forsyth-Edwards Notation.
+    /// Returns false, leaving the game untouched, if the position is not valid.
+    pub fn load_fen(&mut self, fen: &str) -> bool {
+        match Game::from_fen(fen) {
+            Ok(game) => { *self = game; true },
+            Err(_) => false,
+        }
+    }
+}
+
+fn flatten_positions(positions: &[(Piece, u8, u8)]) -> Vec<u8> {
+    positions.iter()
+        .flat_map(|&(piece, x, y)| [piece_code(piece), x, y])
+        .collect()
+}
+
+fn piece_code(piece: Piece) -> u8 {
+    match piece {
+        Piece::Pawn   => 0,
+        Piece::Knight => 1,
+        Piece::Bishop => 2,
+        Piece::Rook   => 3,
+        Piece::Queen  => 4,
+        Piece::King   => 5,
+    }
+}
+
+fn code_piece(code: u8) -> Option<Piece> {
+    match code {
+        0 => Some(Piece::Pawn),
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        5 => Some(Piece::King),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::game::*;
+
+    #[test]
+    fn flat() {
+        let mut game = Game::new();
+
+        let mut moves = game.legal_moves_flat(1, 0); // knight on b1
+        moves.sort();
+        assert_eq!(moves, [0, 2, 2, 2]);
+        assert!(game.legal_moves_flat(1, 7).is_empty()); // opponent
+        assert!(game.legal_moves_flat(8, 0).is_empty());
+
+        let white = game.white_positions_flat();
+        assert_eq!(white.len(), 16 * 3);
+        assert!(white.chunks(3).any(|c| c == [5, 4, 0]));
+
+        assert!(game.select_piece_flat(4, 1));
+        assert_eq!(game.state_flat(), 1);
+        assert!(game.select_move_flat(4, 3));
+        assert_eq!(game.current_player_flat(), 1);
+        assert!(!game.select_move_flat(4, 4));
+        assert!(!game.select_promotion_flat(4));
+
+        assert!(!game.load_fen("not a fen"));
+        assert_eq!(game.ply(), 1);
+        assert!(game.load_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(game.select_piece_flat(0, 6));
+        assert!(game.select_move_flat(0, 7));
+        assert_eq!(game.state_flat(), 3);
+        assert!(!game.select_promotion_flat(5));
+        assert!(game.select_promotion_flat(1));
+        assert!(game.black_positions_flat() == [5, 4, 7]);
+        assert!(game.white_positions_flat().chunks(3).any(|c| c == [1, 0, 7]));
+    }
+}